@@ -0,0 +1,25 @@
+out vec4 color;
+
+// Raw samples: iteration count (negative inside the set), final z and distance estimate in pixels
+uniform sampler2D samples;
+// 0: distance, 1: smooth, 2: edges
+uniform int mode;
+uniform float palette_offset;
+// Iteration count at which the distance coloring completes a cycle, `Coloring::DEPTH_SCALE`
+const float depth_scale = 200.0;
+
+void main() {
+    vec4 s = texelFetch(samples, ivec2(gl_FragCoord.xy), 0);
+    float depth = s.r;
+    if (depth < 0.0) {
+        color = vec4(0.0, 0.0, 0.0, 1.0);
+    } else if (mode == 0) {
+        float dist = min(s.a / 0.15, 1.0);
+        color = vec4(colormap(fract(sqrt(depth / depth_scale) + palette_offset)).rgb * dist, 1.0);
+    } else if (mode == 1) {
+        float smooth_depth = depth + 1.0 - log2(log(length(s.gb)));
+        color = colormap(fract(smooth_depth / 32.0 + palette_offset));
+    } else {
+        color = s.a > 0.25 ? vec4(1.0) : vec4(0.0, 0.0, 0.0, 1.0);
+    }
+}
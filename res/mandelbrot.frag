@@ -1,10 +1,18 @@
-out vec4 color;
+// Iteration count, final z and distance estimate in pixels, a negative count inside the set
+out vec4 result;
 
 uniform vec2 offset;
 uniform vec2 window_size;
 uniform float scale;
+// Cosine and sine of the view angle, see `View::pixels_to_plane`
+uniform vec2 rotation;
+// 0: mandelbrot, 1: tricorn, 2: burning ship, see `Formula::fold`
+uniform int formula;
+uniform uint max_depth;
 
 void main() {
+    vec2 p = (gl_FragCoord.xy - window_size*0.5)*scale;
+    vec2 c = vec2(p.x*rotation.x + p.y*rotation.y, p.y*rotation.x - p.x*rotation.y) + offset;
     float z_real_sq = 0.0;
     float z_imag_sq = 0.0;
     float z_real = 0.0;
@@ -13,23 +21,31 @@ void main() {
     float z_prime_imag = 0.0;
     float z_prime_rtmp;
     uint i = 0u;
-    uint max_depth = 200u;
     while (i < max_depth && (z_real_sq + z_imag_sq) < 4.0) {
+        if (formula == 1) {
+            z_imag = -z_imag;
+            z_prime_imag = -z_prime_imag;
+        } else if (formula == 2) {
+            z_prime_real *= sign(z_real);
+            z_prime_imag *= sign(z_imag);
+            z_real = abs(z_real);
+            z_imag = abs(z_imag);
+        }
         z_prime_rtmp = z_prime_real;
         z_prime_real = 2.0*(z_real*z_prime_real - z_imag*z_prime_imag) + 1.0;
         z_prime_imag = 2.0*(z_real*z_prime_imag + z_imag*z_prime_rtmp);
-        z_imag = 2.0*z_real*z_imag + (gl_FragCoord.y - window_size.y*0.5)*scale + offset.y;
-        z_real = z_real_sq - z_imag_sq + (gl_FragCoord.x - window_size.x*0.5)*scale + offset.x;
+        z_imag = 2.0*z_real*z_imag + c.y;
+        z_real = z_real_sq - z_imag_sq + c.x;
         z_real_sq = z_real * z_real;
         z_imag_sq = z_imag * z_imag;
         i += 1u;
     }
     if(i == max_depth) {
-        color = vec4(0.0, 0.0, 0.0, 1.0);
+        result = vec4(-1.0, 0.0, 0.0, 0.0);
     } else {
         float z_mag = sqrt(z_real_sq + z_imag_sq);
         float z_prime_mag = sqrt(z_prime_real*z_prime_real + z_prime_imag*z_prime_imag);
-        float dist = min((z_mag*log(z_mag)/z_prime_mag) / (0.15*scale), 1.0);
-        color = vec4(0.0, 0.0, 0.0, 1.0) * (1.0-dist) + colormap(sqrt(float(i) / float(max_depth))) * dist;
+        float dist = (z_mag*log(z_mag)/z_prime_mag) / scale;
+        result = vec4(float(i), z_real, z_imag, dist);
     }
 }
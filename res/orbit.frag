@@ -0,0 +1,8 @@
+out vec4 color;
+
+// Straight alpha, blended over the colored fractal
+uniform vec4 line_color;
+
+void main() {
+    color = line_color;
+}
@@ -0,0 +1,16 @@
+#version 140
+// Orbit points in the complex plane
+in vec2 position;
+
+// The view as in mandelbrot.frag
+uniform vec2 offset;
+uniform vec2 window_size;
+uniform float scale;
+uniform vec2 rotation;
+
+void main() {
+    // Inverse of the pixel to complex mapping in mandelbrot.frag
+    vec2 d = (position - offset)/scale;
+    vec2 p = vec2(d.x*rotation.x - d.y*rotation.y, d.x*rotation.y + d.y*rotation.x);
+    gl_Position = vec4(2.0*p/window_size, 0.0, 1.0);
+}
@@ -0,0 +1,30 @@
+#version 140
+in vec2 v_tex_coords;
+out vec4 color;
+
+// Signed distance field atlas, 0.5 is the glyph edge
+uniform sampler2D atlas;
+uniform vec4 text_color;
+uniform vec4 outline_color;
+// Outline thickness in distance field units (0.0 - 0.5)
+uniform float outline_width;
+uniform vec4 shadow_color;
+// Shadow displacement in atlas texture coordinates
+uniform vec2 shadow_offset;
+
+void main() {
+    float dist = texture(atlas, v_tex_coords).r;
+    float aa = fwidth(dist) * 0.75;
+    float fill = smoothstep(0.5 - aa, 0.5 + aa, dist);
+    float outline_edge = 0.5 - outline_width;
+    float body = smoothstep(outline_edge - aa, outline_edge + aa, dist);
+    float shadow_dist = texture(atlas, v_tex_coords - shadow_offset).r;
+    float shadow = smoothstep(outline_edge - 0.1, outline_edge + 0.1, shadow_dist) * shadow_color.a;
+
+    // Fill over outline, then the glyph over its shadow
+    vec4 glyph = mix(outline_color, text_color, fill);
+    float glyph_alpha = glyph.a * body;
+    float alpha = glyph_alpha + shadow * (1.0 - glyph_alpha);
+    vec3 rgb = glyph.rgb * glyph_alpha + shadow_color.rgb * shadow * (1.0 - glyph_alpha);
+    color = vec4(rgb / max(alpha, 1e-5), alpha);
+}
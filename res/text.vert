@@ -0,0 +1,12 @@
+#version 140
+in vec2 position;
+in vec2 tex_coords;
+out vec2 v_tex_coords;
+
+// Text is laid out in window pixels with the origin at the top left
+uniform vec2 window_size;
+
+void main() {
+    v_tex_coords = tex_coords;
+    gl_Position = vec4(2.0*position.x/window_size.x - 1.0, 1.0 - 2.0*position.y/window_size.y, 0.0, 1.0);
+}
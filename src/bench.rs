@@ -0,0 +1,303 @@
+/// Reproducible throughput measurements of the renderers.
+/// A fixed set of views is rendered offscreen with every backend, so results can be compared across machines.
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use clap::Args;
+use glium::Surface;
+use glium::backend::Facade;
+use glium::framebuffer::SimpleFrameBuffer;
+use itertools::Itertools;
+use wide::{f64x4, f64x8};
+
+use crate::coloring::Sample;
+use crate::error::AppError;
+use crate::glium_sdl2::DisplayBuild;
+use crate::fractal::Fractal;
+use crate::kernel::{escape, escape_vector, Lanes};
+use crate::progressive::IncrementalRender;
+use crate::screenshot::samples_from_texels;
+use crate::subdivide::SubdivisionRender;
+use crate::view::{View, WindowSize};
+use crate::{fullscreen_quad, mandelbrot_program, sample_texture, FracFloat};
+
+
+#[derive(Args, Debug)]
+pub struct BenchArgs {
+    /// Resolution of the offscreen renders
+    #[arg(long, default_value_t)]
+    size: WindowSize,
+    /// Number of timed renders per view and backend
+    #[arg(long, default_value_t = 3)]
+    repeat: u32,
+    /// Skip the GPU backend
+    #[arg(long)]
+    cpu_only: bool,
+}
+
+
+/// A standard location in the complex plane.
+pub struct BenchView {
+    pub name: &'static str,
+    pub center: (FracFloat, FracFloat),
+    /// Extent of the complex plane along the shorter image side.
+    pub extent: FracFloat,
+}
+
+impl BenchView {
+    pub fn view(&self, size: (u32, u32)) -> View {
+        View{center: self.center, scale: self.extent / u32::min(size.0, size.1).max(1) as FracFloat, angle: 0.0}
+    }
+}
+
+pub const VIEWS: [BenchView; 3] = [
+    BenchView{name: "full set", center: (-0.5, 0.0), extent: 3.0},
+    BenchView{name: "seahorse valley", center: (-0.745, 0.105), extent: 0.02},
+    // Period 5 minibrot on the real axis. Beyond the resolution of single precision.
+    BenchView{name: "deep minibrot", center: (-1.985424253, 0.0), extent: 2e-4},
+];
+
+
+pub struct BenchResult {
+    pub backend: &'static str,
+    pub precision: &'static str,
+    pub view: &'static str,
+    pub pixels: u64,
+    pub iterations: u64,
+    /// Average time of a single render.
+    pub elapsed: Duration,
+    /// Speedup over the scalar kernel on the same view, none for the scalar kernel itself.
+    pub speedup: Option<f64>,
+}
+
+impl BenchResult {
+    pub fn megapixels_per_sec(&self) -> f64 {
+        self.pixels as f64 / self.elapsed.as_secs_f64() / 1e6
+    }
+
+    pub fn iterations_per_sec(&self) -> f64 {
+        self.iterations as f64 / self.elapsed.as_secs_f64()
+    }
+
+    /// Sets the speedup relative to the `scalar` result of the same view.
+    pub fn compared_to(self, scalar: &BenchResult) -> BenchResult {
+        BenchResult{speedup: Some(scalar.elapsed.as_secs_f64() / self.elapsed.as_secs_f64()), ..self}
+    }
+}
+
+impl fmt::Display for BenchResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f, "{:<10}{:<10}{:<18}{:>10.2}ms{:>12.2}{:>14.3e}",
+            self.backend, self.precision, self.view,
+            self.elapsed.as_secs_f64() * 1000.0, self.megapixels_per_sec(), self.iterations_per_sec()
+        )?;
+        match self.speedup {
+            Some(speedup) => write!(f, "{:>9.2}x", speedup),
+            None => write!(f, "{:>10}", "-"),
+        }
+    }
+}
+
+
+/// Runs all benchmarks and prints the results as a table.
+/// The fractal options of the command line apply, the views stay fixed.
+pub fn run(cli: &crate::Args, args: &BenchArgs) -> Result<(), AppError> {
+    let size = (args.size.width, args.size.height);
+    let repeat = args.repeat.max(1);
+    let fractal = cli.initial_location(size)?.fractal;
+    println!(
+        "Rendering {} at {}x{}, {} iterations max, {} repetitions",
+        fractal.formula, size.0, size.1, fractal.max_iterations, repeat
+    );
+    println!(
+        "{:<10}{:<10}{:<18}{:>12}{:>12}{:>14}{:>10}",
+        "backend", "precision", "view", "time", "Mpixel/s", "iter/s", "speedup"
+    );
+
+    let scalar = VIEWS.iter()
+        .map(|bench_view| bench_cpu("cpu", iteration_count, &fractal, bench_view, size, repeat))
+        .collect::<Vec<_>>();
+    for result in &scalar {
+        println!("{}", result);
+    }
+    let others: [(&'static str, fn(&Fractal, &View, (u32, u32)) -> u64); 3] = [
+        ("cpu-x4", iteration_count_vector::<f64x4, 4>),
+        ("cpu-x8", iteration_count_vector::<f64x8, 8>),
+        ("subdivide", iteration_count_subdivided),
+    ];
+    for (backend, count) in others {
+        for (bench_view, scalar) in VIEWS.iter().zip(&scalar) {
+            println!("{}", bench_cpu(backend, count, &fractal, bench_view, size, repeat).compared_to(scalar));
+        }
+    }
+
+    if args.cpu_only {
+        return Ok(());
+    }
+    match bench_gpu(&fractal, size, repeat, &scalar) {
+        Err(err) if err.is_gl_unavailable() => {
+            println!("Skipping GPU benchmark: {}", err);
+            Ok(())
+        },
+        result => result,
+    }
+}
+
+
+/// `count` renders a view and returns its total number of iterations.
+fn bench_cpu(
+    backend: &'static str,
+    count: fn(&Fractal, &View, (u32, u32)) -> u64,
+    fractal: &Fractal,
+    bench_view: &BenchView,
+    size: (u32, u32),
+    repeat: u32
+) -> BenchResult {
+    let view = bench_view.view(size);
+    let start = Instant::now();
+    let mut iterations = 0;
+    for _ in 0..repeat {
+        iterations = count(fractal, &view, size);
+    }
+    BenchResult{
+        backend,
+        precision: "f64",
+        view: bench_view.name,
+        pixels: size.0 as u64 * size.1 as u64,
+        iterations,
+        elapsed: start.elapsed() / repeat,
+        speedup: None,
+    }
+}
+
+
+/// Total number of iterations over all pixels, points inside the set count with the iteration cap.
+fn iteration_count(fractal: &Fractal, view: &View, size: (u32, u32)) -> u64 {
+    (0..size.0).cartesian_product(0..size.1)
+        .map(|(x, y)| {
+            let c = view.pixel_to_complex(x as FracFloat, y as FracFloat, size);
+            escape(fractal, c).map_or(fractal.max_iterations, |escape| escape.depth) as u64
+        })
+        .sum()
+}
+
+
+/// SIMD kernel with vectors `V` of `N` points, `f64x8` is the one used by the CPU renderers.
+fn iteration_count_vector<V: Lanes<N>, const N: usize>(fractal: &Fractal, view: &View, size: (u32, u32)) -> u64 {
+    let mut total = 0;
+    for y in 0..size.1 {
+        for x0 in (0..size.0).step_by(N) {
+            let mut c_real = [0.0; N];
+            let mut c_imag = [0.0; N];
+            for lane in 0..N {
+                (c_real[lane], c_imag[lane]) = view.pixel_to_complex((x0 as usize + lane) as FracFloat, y as FracFloat, size);
+            }
+            total += (x0..size.0).zip(escape_vector::<V, N>(fractal, &c_real, &c_imag))
+                .map(|(_, escape)| escape.map_or(fractal.max_iterations, |escape| escape.depth) as u64)
+                .sum::<u64>();
+        }
+    }
+    total
+}
+
+
+/// Iterations of the filled pixels are counted as well, as if they were computed.
+fn iteration_count_subdivided(fractal: &Fractal, view: &View, size: (u32, u32)) -> u64 {
+    let mut render = SubdivisionRender::new(*view, size, *fractal);
+    render.refine(Duration::MAX);
+    sample_iterations(fractal, render.samples())
+}
+
+
+/// Total number of iterations of rendered samples, points inside the set count with the iteration cap.
+fn sample_iterations(fractal: &Fractal, samples: &[Sample]) -> u64 {
+    samples.iter()
+        .map(|sample| if sample.is_inside() { fractal.max_iterations } else { sample.depth } as u64)
+        .sum()
+}
+
+
+/// Renders into a texture of a hidden window.
+/// The shader iterates in single precision, so its iterations are counted from its own samples.
+fn bench_gpu(fractal: &Fractal, size: (u32, u32), repeat: u32, scalar: &[BenchResult]) -> Result<(), AppError> {
+    let sdl_context = sdl2::init().map_err(AppError::Sdl)?;
+    let video_subsystem = sdl_context.video().map_err(AppError::Sdl)?;
+    let gl = video_subsystem.window("latcarf bench", 64, 64).hidden().build_glium()?;
+
+    let indices = glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList);
+    let shader = mandelbrot_program(&gl)?;
+    let vbo = fullscreen_quad(&gl)?;
+    let texture = sample_texture(&gl, size)?;
+    let mut target = SimpleFrameBuffer::new(&gl, &texture)
+        .map_err(|err| AppError::RenderTarget(err.to_string()))?;
+
+    for (bench_view, scalar) in VIEWS.iter().zip(scalar) {
+        let view = bench_view.view(size);
+        let uniforms = uniform!{
+            offset: (view.center.0 as f32, view.center.1 as f32),
+            scale: view.scale as f32,
+            rotation: (view.angle.cos() as f32, view.angle.sin() as f32),
+            window_size: (size.0 as f32, size.1 as f32),
+            formula: fractal.formula as i32,
+            max_depth: fractal.max_iterations,
+        };
+        // Warm up, the first draw may include shader compilation by the driver
+        target.draw(&vbo, &indices, &shader, &uniforms, &Default::default())?;
+        let texels = texture.main_level().first_layer().into_image(None)
+            .expect("sample texture has an image")
+            .raw_read(&glium::Rect{left: 0, bottom: 0, width: size.0, height: size.1});
+        let iterations = sample_iterations(fractal, &samples_from_texels(texels));
+        gl.get_context().finish();
+
+        let start = Instant::now();
+        for _ in 0..repeat {
+            target.draw(&vbo, &indices, &shader, &uniforms, &Default::default())?;
+        }
+        gl.get_context().finish();
+        let result = BenchResult{
+            backend: "gpu",
+            precision: "f32",
+            view: bench_view.name,
+            pixels: size.0 as u64 * size.1 as u64,
+            iterations,
+            elapsed: start.elapsed() / repeat,
+            speedup: None,
+        };
+        println!("{}", result.compared_to(scalar));
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bench_result_rates() {
+        let result = BenchResult{
+            backend: "cpu",
+            precision: "f64",
+            view: "test",
+            pixels: 2_000_000,
+            iterations: 50_000_000,
+            elapsed: Duration::from_millis(500),
+            speedup: None,
+        };
+        assert!((result.megapixels_per_sec() - 4.0).abs() < 1e-9);
+        assert!((result.iterations_per_sec() - 1e8).abs() < 1e-3);
+        let faster = BenchResult{backend: "cpu-x8", elapsed: Duration::from_millis(125), ..result}.compared_to(&result);
+        assert_eq!(faster.speedup, Some(4.0));
+        assert!(faster.to_string().ends_with("4.00x"));
+    }
+
+    #[test]
+    fn test_bench_view_extent() {
+        let view = VIEWS[0].view((1920, 1080));
+        let top = view.pixel_to_complex(960.0, 0.0, (1920, 1080));
+        let bottom = view.pixel_to_complex(960.0, 1080.0, (1920, 1080));
+        assert!((top.1 - bottom.1 - VIEWS[0].extent).abs() < 1e-12);
+    }
+}
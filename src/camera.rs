@@ -0,0 +1,303 @@
+/// Smooth camera of the GPU mode.
+/// Input only moves a target view, the shown view follows it once per frame by the elapsed time,
+/// so motion is the same at any frame rate. Drags keep their momentum after the button is released,
+/// and jumps far away, like resetting the view or recalling a bookmark, become a flight.
+
+use std::f64::consts::{PI, TAU};
+use std::time::Duration;
+
+use crate::view::View;
+use crate::FracFloat;
+
+
+/// Time constant of following the target, in seconds.
+const SMOOTHING: FracFloat = 0.08;
+/// Decay rate of the panning momentum per second.
+const FRICTION: FracFloat = 4.0;
+/// Momentum below this speed in pixels per second stops.
+const MIN_SPEED: FracFloat = 5.0;
+/// A drag released after resting this long in milliseconds has no momentum.
+const RELEASE_DELAY: u32 = 50;
+/// Longest time step, so that the first frame after idling does not jump.
+const MAX_STEP: Duration = Duration::from_millis(50);
+/// Target changes larger than this factor in scale, or this many screens away, are flown to.
+const FLIGHT_ZOOM: FracFloat = 8.0;
+const FLIGHT_SCREENS: FracFloat = 1.0;
+
+
+pub struct Camera {
+    /// The view shown in the current frame.
+    view: View,
+    /// Target in the previous frame, to notice jumps.
+    last_target: View,
+    flight: Option<Flight>,
+    dragging: bool,
+    /// Timestamp of the last drag motion in milliseconds.
+    last_motion: Option<u32>,
+    /// Panning speed in pixels per second.
+    velocity: (FracFloat, FracFloat),
+}
+
+impl Camera {
+    pub fn new(view: View) -> Camera {
+        Camera{view, last_target: view, flight: None, dragging: false, last_motion: None, velocity: (0.0, 0.0)}
+    }
+
+    pub fn view(&self) -> View {
+        self.view
+    }
+
+    /// Whether the shown view still changes without further input.
+    pub fn is_moving(&self) -> bool {
+        self.view != self.last_target || self.flight.is_some() || self.velocity != (0.0, 0.0)
+    }
+
+    /// Records a drag by pixels at an event timestamp in milliseconds, the target is panned by `navigate`.
+    pub fn drag(&mut self, dx: FracFloat, dy: FracFloat, timestamp: u32) {
+        if !self.dragging {
+            self.dragging = true;
+            self.velocity = (0.0, 0.0);
+        }
+        if let Some(last) = self.last_motion.filter(|last| timestamp > *last) {
+            let dt = (timestamp - last) as FracFloat / 1000.0;
+            // Averaged over the last few motion events, which arrive unevenly
+            self.velocity.0 = 0.5 * (self.velocity.0 + dx / dt);
+            self.velocity.1 = 0.5 * (self.velocity.1 + dy / dt);
+        }
+        self.last_motion = Some(timestamp);
+    }
+
+    /// Follows the target directly without momentum, for pinches which would otherwise keep the speed of a pan before.
+    pub fn hold(&mut self) {
+        self.dragging = true;
+        self.velocity = (0.0, 0.0);
+        self.last_motion = None;
+    }
+
+    /// Ends a drag, which keeps panning with its momentum unless the mouse rested before.
+    pub fn release(&mut self, timestamp: u32) {
+        if self.last_motion.is_none_or(|last| timestamp.saturating_sub(last) > RELEASE_DELAY) {
+            self.velocity = (0.0, 0.0);
+        }
+        self.dragging = false;
+        self.last_motion = None;
+    }
+
+    pub fn resize(&mut self, old_size: (u32, u32), new_size: (u32, u32)) {
+        self.view.resize(old_size, new_size);
+        self.last_target.resize(old_size, new_size);
+        self.flight = None;
+    }
+
+    /// Moves the shown view towards `target` by the time `elapsed` since the last frame and returns it.
+    /// The momentum of a released drag pans the target itself.
+    pub fn advance(&mut self, target: &mut View, size: (u32, u32), elapsed: Duration) -> View {
+        let dt = elapsed.min(MAX_STEP).as_secs_f64() as FracFloat;
+        if !self.dragging && self.velocity != (0.0, 0.0) {
+            target.pan(self.velocity.0 * dt, self.velocity.1 * dt);
+            let decay = (-FRICTION * dt).exp();
+            self.velocity = (self.velocity.0 * decay, self.velocity.1 * decay);
+            if self.velocity.0.hypot(self.velocity.1) < MIN_SPEED {
+                self.velocity = (0.0, 0.0);
+            }
+        }
+        if *target != self.last_target {
+            // Input during a flight takes over from where the flight is
+            self.flight = is_far(&self.view, target, size).then(|| Flight::new(self.view, *target, size));
+            self.last_target = *target;
+        }
+
+        if self.dragging {
+            self.view = *target;
+        } else if let Some(flight) = &mut self.flight {
+            flight.elapsed += dt;
+            if flight.elapsed < flight.duration {
+                self.view = flight.view();
+            } else {
+                self.view = flight.to;
+                self.flight = None;
+            }
+        } else {
+            let k = 1.0 - (-dt / SMOOTHING).exp();
+            let scale = self.view.scale * (target.scale / self.view.scale).powf(k);
+            let center = (
+                self.view.center.0 + k * (target.center.0 - self.view.center.0),
+                self.view.center.1 + k * (target.center.1 - self.view.center.1),
+            );
+            let angle = (self.view.angle + k * turn(self.view.angle, target.angle)).rem_euclid(TAU);
+            self.view = View{center, scale, angle};
+            // Close enough to not be visible, a fraction of a pixel
+            let offset = (target.center.0 - center.0).hypot(target.center.1 - center.1) / target.scale;
+            if offset < 0.05 && (scale / target.scale).ln().abs() < 1e-3 && turn(angle, target.angle).abs() < 1e-4 {
+                self.view = *target;
+            }
+        }
+        self.view
+    }
+}
+
+
+/// Signed angle of the shorter turn from `from` to `to`.
+fn turn(from: FracFloat, to: FracFloat) -> FracFloat {
+    (to - from + PI).rem_euclid(TAU) - PI
+}
+
+
+/// Whether `to` is too far from `from` to be followed smoothly.
+fn is_far(from: &View, to: &View, size: (u32, u32)) -> bool {
+    let screen = u32::min(size.0, size.1) as FracFloat * from.scale;
+    let distance = (to.center.0 - from.center.0).hypot(to.center.1 - from.center.1);
+    (to.scale / from.scale).ln().abs() > FLIGHT_ZOOM.ln() || distance > FLIGHT_SCREENS * screen
+}
+
+
+/// Animated move between two views, zooming out far enough on the way to see both ends.
+struct Flight {
+    from: View,
+    to: View,
+    /// Additional zoom out in the middle, as the natural logarithm of the scale.
+    bump: FracFloat,
+    elapsed: FracFloat,
+    duration: FracFloat,
+}
+
+impl Flight {
+    /// Number of steps integrating the scale along the flight.
+    const STEPS: usize = 64;
+
+    fn new(from: View, to: View, size: (u32, u32)) -> Flight {
+        let distance = (to.center.0 - from.center.0).hypot(to.center.1 - from.center.1);
+        let (log_from, log_to) = (from.scale.ln(), to.scale.ln());
+        // Scale at which both centers fit on the shorter side, unless one end already shows both
+        let overview = (distance / u32::min(size.0, size.1) as FracFloat).ln();
+        let bump = (overview - log_from.max(log_to)).max(0.0);
+        let path = (log_to - log_from).abs() + 2.0 * bump;
+        Flight{from, to, bump, elapsed: 0.0, duration: (0.5 + 0.08 * path).min(4.0)}
+    }
+
+    fn log_scale(&self, u: FracFloat) -> FracFloat {
+        self.from.scale.ln() + u * (self.to.scale.ln() - self.from.scale.ln()) + self.bump * 4.0 * u * (1.0 - u)
+    }
+
+    /// Integral of the scale over the flight up to `u`.
+    fn travel(&self, u: FracFloat) -> FracFloat {
+        let step = u / Flight::STEPS as FracFloat;
+        (0..Flight::STEPS).map(|i| self.log_scale((i as FracFloat + 0.5) * step).exp() * step).sum()
+    }
+
+    fn view(&self) -> View {
+        let t = (self.elapsed / self.duration).clamp(0.0, 1.0);
+        let u = t * t * (3.0 - 2.0 * t);
+        // The center moves in proportion to the scale, so it crosses the screen at a steady pace
+        let total = self.travel(1.0);
+        let w = if total > 0.0 { self.travel(u) / total } else { u };
+        View{
+            center: (
+                self.from.center.0 + w * (self.to.center.0 - self.from.center.0),
+                self.from.center.1 + w * (self.to.center.1 - self.from.center.1),
+            ),
+            scale: self.log_scale(u).exp(),
+            angle: (self.from.angle + u * turn(self.from.angle, self.to.angle)).rem_euclid(TAU),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: (u32, u32) = (800, 600);
+    const FRAME: Duration = Duration::from_millis(16);
+
+    fn settle(camera: &mut Camera, target: &mut View, frames: usize) {
+        for _ in 0..frames {
+            camera.advance(target, SIZE, FRAME);
+        }
+    }
+
+    #[test]
+    fn test_follow_is_smooth() {
+        let home = View::fit(SIZE);
+        let mut camera = Camera::new(home);
+        let mut target = home;
+        target.zoom(1.25);
+        target.rotate(0.5);
+        let first = camera.advance(&mut target, SIZE, FRAME);
+        assert!(first.scale < home.scale && first.scale > target.scale);
+        assert!(first.angle > 0.0 && first.angle < 0.5);
+        assert!(camera.is_moving());
+        settle(&mut camera, &mut target, 100);
+        assert_eq!(camera.view(), target);
+        assert!(!camera.is_moving());
+    }
+
+    #[test]
+    fn test_momentum() {
+        let home = View::fit(SIZE);
+        let mut camera = Camera::new(home);
+        let mut target = home;
+        for timestamp in [0, 10, 20, 30] {
+            target.pan(10.0, 0.0);
+            camera.drag(10.0, 0.0, timestamp);
+        }
+        camera.release(35);
+        let released = target;
+        settle(&mut camera, &mut target, 200);
+        assert!(target.center.0 < released.center.0);
+        assert!(!camera.is_moving());
+
+        // No momentum when the mouse rests before the release
+        camera.drag(10.0, 0.0, 1000);
+        camera.drag(10.0, 0.0, 1010);
+        camera.release(1200);
+        let released = target;
+        settle(&mut camera, &mut target, 10);
+        assert_eq!(target, released);
+    }
+
+    #[test]
+    fn test_pinch_after_pan_has_no_momentum() {
+        let home = View::fit(SIZE);
+        let mut camera = Camera::new(home);
+        let mut target = home;
+        for timestamp in [0, 10, 20] {
+            target.pan(10.0, 0.0);
+            camera.drag(10.0, 0.0, timestamp);
+        }
+        // A second finger touches down and pinches
+        camera.hold();
+        target.zoom(1.1);
+        camera.hold();
+        assert_eq!(camera.advance(&mut target, SIZE, FRAME), target);
+        camera.release(35);
+        let released = target;
+        settle(&mut camera, &mut target, 10);
+        assert_eq!(target, released);
+        assert!(!camera.is_moving());
+    }
+
+    #[test]
+    fn test_flight() {
+        let home = View::fit(SIZE);
+        let deep = View{center: (-0.743643887, 0.131825904), scale: 1e-12, angle: 6.0};
+        let mut camera = Camera::new(deep);
+        let mut target = home;
+        let mut max_scale: FracFloat = 0.0;
+        for _ in 0..300 {
+            max_scale = max_scale.max(camera.advance(&mut target, SIZE, FRAME).scale);
+        }
+        assert_eq!(camera.view(), home);
+        assert!(max_scale <= home.scale * 1.0001);
+
+        // Far apart at the same scale: zooms out on the way
+        let far = View{center: (1.0, 0.0), scale: 1e-6, angle: 0.0};
+        let mut camera = Camera::new(View{center: (-1.0, 0.0), scale: 1e-6, angle: 0.0});
+        let mut target = far;
+        camera.advance(&mut target, SIZE, FRAME);
+        settle(&mut camera, &mut target, 60);
+        assert!(camera.view().scale > 1e-3);
+        settle(&mut camera, &mut target, 300);
+        assert_eq!(camera.view(), far);
+    }
+}
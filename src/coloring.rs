@@ -0,0 +1,282 @@
+/// Coloring of raw iteration results.
+/// Renderers only produce samples, which are colored in a separate pass. Changing the coloring
+/// therefore does not require computing the fractal again. `res/coloring.frag` implements the same
+/// coloring on the GPU, the colormaps are ports of the GLSL colormaps in `res/`.
+
+use std::f32::consts::{LN_2, PI};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::kernel::Escape;
+use crate::FracFloat;
+
+
+/// Raw result of a pixel, in single precision like the sample texture of the GPU renderer.
+/// The magnitude of the derivative follows from the distance estimate, `|z| ln|z| / (dist * scale)`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sample {
+    /// Iterations until escape, `u32::MAX` for points in the set.
+    pub depth: u32,
+    /// Final z.
+    pub z: [f32; 2],
+    /// Distance estimate in pixels.
+    pub dist: f32,
+}
+
+impl Sample {
+    pub const INSIDE: Sample = Sample{depth: u32::MAX, z: [0.0, 0.0], dist: 0.0};
+
+    /// Sample of a kernel result, for a view with `scale` complex units per pixel.
+    pub fn new(escape: Option<Escape>, scale: FracFloat) -> Sample {
+        match escape {
+            Some(escape) => Sample{
+                depth: escape.depth,
+                z: [escape.z.0 as f32, escape.z.1 as f32],
+                dist: (escape.dist / scale) as f32,
+            },
+            None => Sample::INSIDE,
+        }
+    }
+
+    pub fn is_inside(&self) -> bool {
+        self.depth == u32::MAX
+    }
+
+    /// Continuous iteration count without the bands of the integer count.
+    pub fn smooth_depth(&self) -> f32 {
+        let z_mag = (self.z[0]*self.z[0] + self.z[1]*self.z[1]).sqrt();
+        self.depth as f32 + 1.0 - z_mag.ln().ln() / LN_2
+    }
+}
+
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ColorMode {
+    /// Colormap by iteration count, darkened close to the set.
+    #[default]
+    Distance,
+    /// Colormap by smooth iteration count.
+    Smooth,
+    /// White outside, black within a quarter pixel of the set.
+    Edges,
+}
+
+impl ColorMode {
+    pub const ALL: [ColorMode; 3] = [ColorMode::Distance, ColorMode::Smooth, ColorMode::Edges];
+
+    pub fn next(&self) -> ColorMode {
+        ColorMode::ALL[(*self as usize + 1) % ColorMode::ALL.len()]
+    }
+}
+
+impl fmt::Display for ColorMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ColorMode::Distance => write!(f, "distance"),
+            ColorMode::Smooth => write!(f, "smooth"),
+            ColorMode::Edges => write!(f, "edges"),
+        }
+    }
+}
+
+impl FromStr for ColorMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ColorMode, String> {
+        ColorMode::ALL.into_iter()
+            .find(|mode| mode.to_string() == s)
+            .ok_or_else(|| format!("Expected 'distance', 'smooth' or 'edges', got '{}'", s))
+    }
+}
+
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Colormap {
+    #[default]
+    Waves,
+    Pastel1,
+}
+
+impl Colormap {
+    pub const ALL: [Colormap; 2] = [Colormap::Waves, Colormap::Pastel1];
+
+    pub fn next(&self) -> Colormap {
+        Colormap::ALL[(*self as usize + 1) % Colormap::ALL.len()]
+    }
+
+    /// GLSL source defining `vec4 colormap(float x)`.
+    pub fn shader_source(&self) -> &'static [u8] {
+        match self {
+            Colormap::Waves => include_bytes!("../res/IDL_Waves.frag"),
+            Colormap::Pastel1 => include_bytes!("../res/IDL_CB-Pastel1.frag"),
+        }
+    }
+
+    /// RGB color for `x` in 0..1.
+    pub fn color(&self, x: f32) -> [f32; 3] {
+        let rgb = match self {
+            Colormap::Waves => {
+                let wave = |x: f32, phase: f32| 126.963_45 * (2.0 * PI / 1.011_727_7 * x + 2.0 * PI * (0.003_851_232 + phase)).sin() + 127.527_75;
+                let green = 63.194_607 * (2.0 * PI / 0.063_237_466 * x + 2.0 * PI * 0.062_084_436).sin() + 96.563_05;
+                [wave(x, 0.5), green, wave(x, 0.0)]
+            },
+            Colormap::Pastel1 => [
+                piecewise_linear(&PASTEL1_RED, x),
+                piecewise_linear(&PASTEL1_GREEN, x),
+                piecewise_linear(&PASTEL1_BLUE, x),
+            ],
+        };
+        rgb.map(|v| (v / 255.0).clamp(0.0, 1.0))
+    }
+}
+
+impl fmt::Display for Colormap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Colormap::Waves => write!(f, "waves"),
+            Colormap::Pastel1 => write!(f, "pastel1"),
+        }
+    }
+}
+
+impl FromStr for Colormap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Colormap, String> {
+        Colormap::ALL.into_iter()
+            .find(|colormap| colormap.to_string() == s)
+            .ok_or_else(|| format!("Expected 'waves' or 'pastel1', got '{}'", s))
+    }
+}
+
+
+/// Segments of a piecewise linear function as (upper bound, slope, intercept).
+type Segments = [(f32, f32, f32)];
+
+fn piecewise_linear(segments: &Segments, x: f32) -> f32 {
+    let (_, slope, intercept) = segments.iter()
+        .find(|(bound, _, _)| x < *bound)
+        .unwrap_or(&segments[segments.len() - 1]);
+    slope * x + intercept
+}
+
+const PASTEL1_RED: [(f32, f32, f32); 8] = [
+    (0.122_867_92, -581.788_5, 250.471_6),
+    (0.244_904_62, 199.984_35, 154.416_8),
+    (0.372_972_9, 143.786_09, 168.18),
+    (0.501_111_6, 252.012_8, 127.814_37),
+    (0.623_928_24, 7.854_505, 250.164_92),
+    (0.752_040_4, -200.555_72, 380.197_95),
+    (0.879_653_5, 186.622_4, 89.024_37),
+    (f32::INFINITY, -93.067_41, 335.054_5),
+];
+
+const PASTEL1_GREEN: [(f32, f32, f32); 7] = [
+    (0.249_880_15, 221.725_71, 179.002_48),
+    (0.373_516_77, -252.975_8, 297.620_97),
+    (0.500_787_2, 109.439_27, 162.252_86),
+    (0.626_227_46, 302.956_45, 65.341_94),
+    (0.752_848_7, -310.470_3, 449.486_62),
+    (0.882_750_4, 22.767_507, 198.608_96),
+    (f32::INFINITY, 195.678_7, 45.971_54),
+];
+
+const PASTEL1_BLUE: [(f32, f32, f32); 8] = [
+    (0.123_298_96, 429.695_75, 174.153_4),
+    (0.247_631_43, -240.499_27, 256.787_76),
+    (0.374_236_1, 241.095_16, 137.529_84),
+    (0.499_859_45, -490.936_5, 411.482_5),
+    (0.625_635_1, 296.955_9, 17.647_058),
+    (0.752_550_95, -111.771_3, 273.361_14),
+    (0.878_596_9, 373.063_7, -91.501_91),
+    (f32::INFINITY, 45.544_83, 196.255_17),
+];
+
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Coloring {
+    pub mode: ColorMode,
+    pub colormap: Colormap,
+    /// Shift along the colormap, wrapping around at 1.
+    pub offset: f32,
+}
+
+impl Coloring {
+    /// Iterations per cycle through the colormap in smooth mode.
+    const SMOOTH_PERIOD: f32 = 32.0;
+    /// Iteration count at the end of the colormap in distance mode. Independent of the iteration cap,
+    /// so that raising it does not change the colors of the points that escaped before.
+    const DEPTH_SCALE: f32 = 200.0;
+
+    /// sRGB color with channels in 0..1, before quantization.
+    pub fn color_f32(&self, sample: &Sample) -> [f32; 3] {
+        if sample.is_inside() {
+            return [0.0; 3];
+        }
+        match self.mode {
+            ColorMode::Distance => {
+                let shade = (sample.dist / 0.15).min(1.0);
+                let x = (sample.depth as f32 / Coloring::DEPTH_SCALE).sqrt() + self.offset;
+                self.colormap.color(x.rem_euclid(1.0)).map(|v| v * shade)
+            },
+            ColorMode::Smooth => {
+                let x = sample.smooth_depth() / Coloring::SMOOTH_PERIOD + self.offset;
+                self.colormap.color(x.rem_euclid(1.0))
+            },
+            ColorMode::Edges => if sample.dist > 0.25 { [1.0; 3] } else { [0.0; 3] },
+        }
+    }
+
+    pub fn color(&self, sample: &Sample) -> [u8; 3] {
+        self.color_f32(sample).map(|v| (v * 255.0).round() as u8)
+    }
+
+    /// Colors all samples into an RGB24 image.
+    pub fn apply(&self, samples: &[Sample], rgb: &mut [u8]) {
+        for (sample, pixel) in samples.iter().zip(rgb.chunks_exact_mut(3)) {
+            pixel.copy_from_slice(&self.color(sample));
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_coloring_modes() {
+        let far = Sample{depth: 10, z: [3.0, 0.0], dist: 5.0};
+        let near = Sample{dist: 0.1, ..far};
+        let edges = Coloring{mode: ColorMode::Edges, ..Default::default()};
+        assert_eq!(edges.color(&far), [255, 255, 255]);
+        assert_eq!(edges.color(&near), [0, 0, 0]);
+        for mode in ColorMode::ALL {
+            let coloring = Coloring{mode, ..Default::default()};
+            assert_eq!(coloring.color(&Sample::INSIDE), [0, 0, 0]);
+        }
+        // Offsetting by a full cycle gives the same color
+        let smooth = Coloring{mode: ColorMode::Smooth, ..Default::default()};
+        assert_eq!(smooth.color(&far), Coloring{offset: 1.0, ..smooth}.color(&far));
+    }
+
+    #[test]
+    fn test_smooth_depth_is_continuous() {
+        // A point that just escaped and one that is one iteration further with z squared
+        let before = Sample{depth: 5, z: [2.0, 0.0], dist: 1.0};
+        let after = Sample{depth: 6, z: [4.0, 0.0], dist: 1.0};
+        assert!((before.smooth_depth() - after.smooth_depth()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_color_settings_parse() {
+        for mode in ColorMode::ALL {
+            assert_eq!(mode.to_string().parse(), Ok(mode));
+        }
+        for colormap in Colormap::ALL {
+            assert_eq!(colormap.to_string().parse(), Ok(colormap));
+        }
+        assert_eq!(Colormap::Pastel1.next(), Colormap::Waves);
+        assert!("rainbow".parse::<Colormap>().is_err());
+    }
+}
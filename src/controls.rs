@@ -0,0 +1,354 @@
+/// Keyboard controls of the interactive modes.
+/// Keys are looked up in a binding table of actions, which `--bind ACTION=KEY` changes.
+/// Mouse navigation is handled separately, see `navigate` in `main.rs`, and so are the bookmarks on the number keys.
+
+use std::fmt;
+use std::str::FromStr;
+
+use sdl2::keyboard::{Keycode, Mod};
+
+use crate::location::Location;
+use crate::FracFloat;
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    PanLeft,
+    PanRight,
+    PanUp,
+    PanDown,
+    ZoomIn,
+    ZoomOut,
+    RotateLeft,
+    RotateRight,
+    ResetView,
+    MoreIterations,
+    FewerIterations,
+    NextFormula,
+    NextColoring,
+    NextColormap,
+    PaletteBack,
+    PaletteForward,
+    Screenshot,
+    Fullscreen,
+    Help,
+    Orbit,
+}
+
+impl Action {
+    pub const ALL: [Action; 20] = [
+        Action::PanLeft, Action::PanRight, Action::PanUp, Action::PanDown, Action::ZoomIn, Action::ZoomOut,
+        Action::RotateLeft, Action::RotateRight, Action::ResetView, Action::MoreIterations, Action::FewerIterations, Action::NextFormula,
+        Action::NextColoring, Action::NextColormap, Action::PaletteBack, Action::PaletteForward,
+        Action::Screenshot, Action::Fullscreen, Action::Help, Action::Orbit,
+    ];
+    /// Fraction of the shorter window side moved per key press.
+    const PAN_STEP: FracFloat = 0.1;
+    const ZOOM_STEP: FracFloat = 1.25;
+    /// Rotation per key press in radians, 5 degrees.
+    const ROTATE_STEP: FracFloat = std::f64::consts::PI / 36.0;
+    const PALETTE_STEP: f32 = 0.02;
+
+    fn description(&self) -> &'static str {
+        match self {
+            Action::PanLeft => "pan left",
+            Action::PanRight => "pan right",
+            Action::PanUp => "pan up",
+            Action::PanDown => "pan down",
+            Action::ZoomIn => "zoom in",
+            Action::ZoomOut => "zoom out",
+            Action::RotateLeft => "rotate counterclockwise",
+            Action::RotateRight => "rotate clockwise",
+            Action::ResetView => "reset view",
+            Action::MoreIterations => "double iterations",
+            Action::FewerIterations => "halve iterations",
+            Action::NextFormula => "next formula",
+            Action::NextColoring => "next coloring",
+            Action::NextColormap => "next colormap",
+            Action::PaletteBack => "shift palette back",
+            Action::PaletteForward => "shift palette forward",
+            Action::Screenshot => "save screenshot",
+            Action::Fullscreen => "toggle fullscreen",
+            Action::Help => "toggle this help",
+            Action::Orbit => "toggle orbit of the pointer, MMB pins it",
+        }
+    }
+
+    /// Whether holding a key repeats the action, only for those that change the location.
+    pub fn repeats(&self) -> bool {
+        !matches!(self, Action::Screenshot | Action::Fullscreen | Action::Help | Action::Orbit)
+    }
+
+    /// Applies actions that change the location, `home` is where the view is reset to.
+    /// Returns false for the remaining actions, which each mode handles itself.
+    pub fn apply(&self, location: &mut Location, home: &Location) -> bool {
+        let pan_step = Action::PAN_STEP * u32::min(location.size.0, location.size.1) as FracFloat;
+        let view = &mut location.view;
+        let coloring = &mut location.coloring;
+        match self {
+            Action::PanLeft => view.pan(pan_step, 0.0),
+            Action::PanRight => view.pan(-pan_step, 0.0),
+            Action::PanUp => view.pan(0.0, pan_step),
+            Action::PanDown => view.pan(0.0, -pan_step),
+            Action::ZoomIn => view.zoom(Action::ZOOM_STEP),
+            Action::ZoomOut => view.zoom(1.0 / Action::ZOOM_STEP),
+            Action::RotateLeft => view.rotate(Action::ROTATE_STEP),
+            Action::RotateRight => view.rotate(-Action::ROTATE_STEP),
+            Action::ResetView => *view = home.view(location.size),
+            Action::MoreIterations => location.fractal.more_iterations(),
+            Action::FewerIterations => location.fractal.fewer_iterations(),
+            Action::NextFormula => location.fractal.formula = location.fractal.formula.next(),
+            Action::NextColoring => coloring.mode = coloring.mode.next(),
+            Action::NextColormap => coloring.colormap = coloring.colormap.next(),
+            Action::PaletteBack => coloring.offset = (coloring.offset - Action::PALETTE_STEP).rem_euclid(1.0),
+            Action::PaletteForward => coloring.offset = (coloring.offset + Action::PALETTE_STEP).rem_euclid(1.0),
+            Action::Screenshot | Action::Fullscreen | Action::Help | Action::Orbit => return false,
+        }
+        true
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Action::PanLeft => "pan-left",
+            Action::PanRight => "pan-right",
+            Action::PanUp => "pan-up",
+            Action::PanDown => "pan-down",
+            Action::ZoomIn => "zoom-in",
+            Action::ZoomOut => "zoom-out",
+            Action::RotateLeft => "rotate-left",
+            Action::RotateRight => "rotate-right",
+            Action::ResetView => "reset-view",
+            Action::MoreIterations => "more-iterations",
+            Action::FewerIterations => "fewer-iterations",
+            Action::NextFormula => "next-formula",
+            Action::NextColoring => "next-coloring",
+            Action::NextColormap => "next-colormap",
+            Action::PaletteBack => "palette-back",
+            Action::PaletteForward => "palette-forward",
+            Action::Screenshot => "screenshot",
+            Action::Fullscreen => "fullscreen",
+            Action::Help => "help",
+            Action::Orbit => "orbit",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Action {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Action, String> {
+        Action::ALL.into_iter()
+            .find(|action| action.to_string() == s)
+            .ok_or_else(|| format!("Unknown action '{}', expected one of {}", s, Action::ALL.map(|action| action.to_string()).join(", ")))
+    }
+}
+
+
+const DEFAULT_BINDINGS: [(Keycode, Action); 30] = [
+    (Keycode::Left, Action::PanLeft),
+    (Keycode::A, Action::PanLeft),
+    (Keycode::Right, Action::PanRight),
+    (Keycode::D, Action::PanRight),
+    (Keycode::Up, Action::PanUp),
+    (Keycode::W, Action::PanUp),
+    (Keycode::Down, Action::PanDown),
+    (Keycode::S, Action::PanDown),
+    (Keycode::Plus, Action::ZoomIn),
+    (Keycode::Equals, Action::ZoomIn),
+    (Keycode::KpPlus, Action::ZoomIn),
+    (Keycode::Minus, Action::ZoomOut),
+    (Keycode::KpMinus, Action::ZoomOut),
+    (Keycode::Q, Action::RotateLeft),
+    (Keycode::E, Action::RotateRight),
+    (Keycode::Home, Action::ResetView),
+    (Keycode::R, Action::ResetView),
+    (Keycode::PageUp, Action::MoreIterations),
+    (Keycode::I, Action::MoreIterations),
+    (Keycode::PageDown, Action::FewerIterations),
+    (Keycode::U, Action::FewerIterations),
+    (Keycode::F, Action::NextFormula),
+    (Keycode::C, Action::NextColoring),
+    (Keycode::M, Action::NextColormap),
+    (Keycode::LeftBracket, Action::PaletteBack),
+    (Keycode::RightBracket, Action::PaletteForward),
+    (Keycode::F12, Action::Screenshot),
+    (Keycode::F11, Action::Fullscreen),
+    (Keycode::H, Action::Help),
+    (Keycode::O, Action::Orbit),
+];
+
+
+/// A key bound on the command line as `ACTION=KEY`, with SDL key names such as "E", "Space" or "Keypad +".
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Binding {
+    pub action: Action,
+    pub key: Keycode,
+}
+
+impl FromStr for Binding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Binding, String> {
+        let (action, key) = s.split_once('=').ok_or_else(|| format!("Expected ACTION=KEY, got '{}'", s))?;
+        Ok(Binding{
+            action: action.trim().parse()?,
+            key: Keycode::from_name(key.trim()).ok_or_else(|| format!("Unknown key '{}'", key))?,
+        })
+    }
+}
+
+
+pub struct Controls {
+    bindings: Vec<(Keycode, Action)>,
+}
+
+impl Controls {
+    /// Default bindings with `overrides` applied. An overridden action loses its default keys,
+    /// and a rebound key no longer triggers its previous action.
+    pub fn new(overrides: &[Binding]) -> Controls {
+        let mut bindings: Vec<(Keycode, Action)> = DEFAULT_BINDINGS.into_iter()
+            .filter(|(_, action)| !overrides.iter().any(|binding| binding.action == *action))
+            .collect();
+        for binding in overrides {
+            bindings.retain(|(key, _)| *key != binding.key);
+            bindings.push((binding.key, binding.action));
+        }
+        Controls{bindings}
+    }
+
+    pub fn action(&self, key: Keycode) -> Option<Action> {
+        self.bindings.iter().find(|(bound, _)| *bound == key).map(|(_, action)| *action)
+    }
+
+    /// Names of the keys bound to `action`, e.g. "Left/A".
+    pub fn keys(&self, action: Action) -> String {
+        let keys: Vec<String> = self.bindings.iter()
+            .filter(|(_, bound)| *bound == action)
+            .map(|(key, _)| key.name())
+            .collect();
+        if keys.is_empty() { "unbound".to_string() } else { keys.join("/") }
+    }
+
+    /// One line per action with its keys.
+    pub fn help_text(&self) -> String {
+        let lines: Vec<String> = Action::ALL.iter()
+            .map(|&action| format!("{:<18}{}", self.keys(action), action.description()))
+            .collect();
+        lines.join("\n") + "\n1-9               fly to bookmark, with Ctrl store it\nDrag with LMB to pan, with RMB to rotate, scroll to zoom"
+    }
+}
+
+
+/// Locations recalled with the number keys 1-9, loaded with `--bookmark` or stored with Ctrl and a number key.
+pub struct Bookmarks {
+    slots: [Option<Location>; 9],
+}
+
+impl Bookmarks {
+    const KEYS: [Keycode; 9] = [
+        Keycode::Num1, Keycode::Num2, Keycode::Num3, Keycode::Num4, Keycode::Num5,
+        Keycode::Num6, Keycode::Num7, Keycode::Num8, Keycode::Num9,
+    ];
+
+    /// Bookmarks in the slots from 1 on, locations beyond the ninth are ignored.
+    pub fn new(locations: Vec<Location>) -> Bookmarks {
+        let mut slots = [None; 9];
+        for (slot, location) in slots.iter_mut().zip(locations) {
+            *slot = Some(location);
+        }
+        Bookmarks{slots}
+    }
+
+    /// Stores the location with Ctrl held, otherwise recalls the bookmark at the current size.
+    /// Returns true if the location changed.
+    pub fn handle_key(&mut self, key: Keycode, keymod: Mod, location: &mut Location) -> bool {
+        let Some(slot) = Bookmarks::KEYS.iter().position(|bookmark_key| *bookmark_key == key) else {
+            return false;
+        };
+        if keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD) {
+            self.slots[slot] = Some(*location);
+            println!("Stored bookmark {}.", slot + 1);
+            return false;
+        }
+        match self.slots[slot] {
+            Some(mut bookmark) => {
+                bookmark.resize(location.size);
+                *location = bookmark;
+                true
+            },
+            None => false,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rebinding() {
+        let controls = Controls::new(&[
+            Binding{action: Action::ZoomIn, key: Keycode::E},
+            Binding{action: Action::Screenshot, key: Keycode::C},
+        ]);
+        assert_eq!(controls.action(Keycode::E), Some(Action::ZoomIn));
+        assert_eq!(controls.keys(Action::RotateRight), "unbound");
+        assert_eq!(controls.action(Keycode::Plus), None);
+        assert_eq!(controls.action(Keycode::C), Some(Action::Screenshot));
+        assert_eq!(controls.action(Keycode::F12), None);
+        assert_eq!(controls.action(Keycode::A), Some(Action::PanLeft));
+    }
+
+    #[test]
+    fn test_action_parse() {
+        for action in Action::ALL {
+            assert_eq!(action.to_string().parse(), Ok(action));
+        }
+        assert!("jump".parse::<Action>().is_err());
+        assert!("zoom-in".parse::<Binding>().is_err());
+    }
+
+    #[test]
+    fn test_apply_actions() {
+        let home = Location{
+            view: crate::view::View::fit((100, 50)),
+            size: (100, 50),
+            fractal: Default::default(),
+            coloring: Default::default(),
+        };
+        let mut location = home;
+        assert!(Action::PanLeft.apply(&mut location, &home));
+        assert!(location.view.center.0 < 0.0);
+        assert!(Action::ZoomIn.apply(&mut location, &home));
+        assert!(Action::MoreIterations.apply(&mut location, &home));
+        assert!(!Action::Screenshot.apply(&mut location, &home));
+        assert!(Action::ResetView.apply(&mut location, &home));
+        assert_eq!(location.view, home.view);
+        assert_ne!(location.fractal, home.fractal);
+    }
+
+    #[test]
+    fn test_bookmarks() {
+        let home = Location{
+            view: crate::view::View::fit((100, 50)),
+            size: (100, 50),
+            fractal: Default::default(),
+            coloring: Default::default(),
+        };
+        let mut bookmarks = Bookmarks::new(vec![home]);
+        let mut location = home;
+        location.resize((200, 100));
+        location.view.zoom(2.0);
+        let zoomed = location;
+        assert!(!bookmarks.handle_key(Keycode::Num2, Mod::LCTRLMOD, &mut location));
+        assert!(bookmarks.handle_key(Keycode::Num1, Mod::NOMOD, &mut location));
+        assert_eq!(location.view, home.view((200, 100)));
+        assert!(bookmarks.handle_key(Keycode::Num2, Mod::NOMOD, &mut location));
+        assert_eq!(location, zoomed);
+        assert!(!bookmarks.handle_key(Keycode::Num3, Mod::NOMOD, &mut location));
+    }
+}
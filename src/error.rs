@@ -0,0 +1,160 @@
+/// Application level errors, reported by `main` before exiting.
+
+use std::fmt;
+use std::io;
+
+use glium::{DrawError, SwapBuffersError};
+use image::ImageError;
+use sdl2::IntegerOrSdlError;
+use sdl2::video::WindowBuildError;
+
+use crate::export::ExportError;
+use crate::glium_sdl2::GliumSdl2Error;
+use crate::location::LocationError;
+use crate::text_rendering::{AtlasError, FontError};
+
+
+#[derive(Debug)]
+pub enum AppError {
+    /// An SDL call failed, e.g. while initializing a subsystem.
+    Sdl(String),
+    Window(WindowBuildError),
+    /// The OpenGL window or context could not be created.
+    Gl(GliumSdl2Error),
+    /// Shader compilation or linking failed. Contains the formatted compiler output.
+    Shader(String),
+    VertexBuffer(glium::vertex::BufferCreationError),
+    /// An offscreen texture or framebuffer could not be created.
+    RenderTarget(String),
+    Draw(DrawError),
+    SwapBuffers(SwapBuffersError),
+    Font(FontError),
+    Atlas(AtlasError),
+    Image(ImageError),
+    Io(io::Error),
+    Export(ExportError),
+    Location(LocationError),
+    /// A combination of options that is not supported.
+    Unsupported(String),
+    InvalidDevice(String),
+}
+
+impl AppError {
+    /// True if the error means that OpenGL is not usable on this machine.
+    /// Creating an OpenGL window fails as well when SDL cannot load the GL library.
+    pub fn is_gl_unavailable(&self) -> bool {
+        matches!(self, AppError::Gl(_))
+    }
+}
+
+impl From<GliumSdl2Error> for AppError {
+    fn from(err: GliumSdl2Error) -> AppError {
+        AppError::Gl(err)
+    }
+}
+
+impl From<WindowBuildError> for AppError {
+    fn from(err: WindowBuildError) -> AppError {
+        AppError::Window(err)
+    }
+}
+
+impl From<IntegerOrSdlError> for AppError {
+    fn from(err: IntegerOrSdlError) -> AppError {
+        AppError::Sdl(err.to_string())
+    }
+}
+
+impl From<glium::vertex::BufferCreationError> for AppError {
+    fn from(err: glium::vertex::BufferCreationError) -> AppError {
+        AppError::VertexBuffer(err)
+    }
+}
+
+impl From<DrawError> for AppError {
+    fn from(err: DrawError) -> AppError {
+        AppError::Draw(err)
+    }
+}
+
+impl From<SwapBuffersError> for AppError {
+    fn from(err: SwapBuffersError) -> AppError {
+        AppError::SwapBuffers(err)
+    }
+}
+
+impl From<FontError> for AppError {
+    fn from(err: FontError) -> AppError {
+        AppError::Font(err)
+    }
+}
+
+impl From<AtlasError> for AppError {
+    fn from(err: AtlasError) -> AppError {
+        AppError::Atlas(err)
+    }
+}
+
+impl From<ImageError> for AppError {
+    fn from(err: ImageError) -> AppError {
+        AppError::Image(err)
+    }
+}
+
+impl From<io::Error> for AppError {
+    fn from(err: io::Error) -> AppError {
+        AppError::Io(err)
+    }
+}
+
+impl From<ExportError> for AppError {
+    fn from(err: ExportError) -> AppError {
+        AppError::Export(err)
+    }
+}
+
+impl From<LocationError> for AppError {
+    fn from(err: LocationError) -> AppError {
+        AppError::Location(err)
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AppError::Sdl(err) => write!(f, "SDL error: {}", err),
+            AppError::Window(err) => write!(f, "Failed to create window: {}", err),
+            AppError::Gl(err) => write!(f, "OpenGL initialization failed: {}", err),
+            AppError::Shader(err) => write!(f, "Failed to compile shader:\n{}", err),
+            AppError::VertexBuffer(err) => write!(f, "Failed to create vertex buffer: {}", err),
+            AppError::RenderTarget(err) => write!(f, "Failed to create render target: {}", err),
+            AppError::Draw(err) => write!(f, "Draw call failed: {}", err),
+            AppError::SwapBuffers(err) => write!(f, "Failed to present frame: {}", err),
+            AppError::Font(err) => err.fmt(f),
+            AppError::Atlas(err) => err.fmt(f),
+            AppError::Image(err) => write!(f, "Image error: {}", err),
+            AppError::Io(err) => write!(f, "I/O error: {}", err),
+            AppError::Export(err) => err.fmt(f),
+            AppError::Location(err) => err.fmt(f),
+            AppError::Unsupported(reason) => write!(f, "Unsupported options: {}", reason),
+            AppError::InvalidDevice(device) => write!(f, "Invalid device '{}', expected 'cpu' or 'gpu'", device),
+        }
+    }
+}
+
+impl std::error::Error for AppError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AppError::Window(err) => Some(err),
+            AppError::Gl(err) => Some(err),
+            AppError::Draw(err) => Some(err),
+            AppError::Font(err) => Some(err),
+            AppError::Atlas(err) => Some(err),
+            AppError::Image(err) => Some(err),
+            AppError::Io(err) => Some(err),
+            AppError::Export(err) => Some(err),
+            AppError::Location(err) => Some(err),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,254 @@
+/// Export of raw samples for analysis outside of latcarf.
+/// Iteration counts are written as 32-bit unsigned integers, smooth iteration counts and distance
+/// estimates (in pixels) as 32-bit floats, little endian and with the top image row first.
+/// Points inside the set have the iteration cap as iterations, a NaN smooth count and distance 0.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use png::EncodingError;
+use tiff::encoder::{colortype, TiffEncoder};
+use tiff::TiffError;
+
+use crate::coloring::Sample;
+use crate::location::Location;
+
+
+#[derive(Debug)]
+pub enum ExportError {
+    Io(io::Error),
+    Tiff(TiffError),
+    Png(EncodingError),
+}
+
+impl From<io::Error> for ExportError {
+    fn from(err: io::Error) -> ExportError {
+        ExportError::Io(err)
+    }
+}
+
+impl From<TiffError> for ExportError {
+    fn from(err: TiffError) -> ExportError {
+        ExportError::Tiff(err)
+    }
+}
+
+impl From<EncodingError> for ExportError {
+    fn from(err: EncodingError) -> ExportError {
+        ExportError::Png(err)
+    }
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExportError::Io(err) => write!(f, "Failed to export samples: {}", err),
+            ExportError::Tiff(err) => write!(f, "Failed to export samples as TIFF: {}", err),
+            ExportError::Png(err) => write!(f, "Failed to write PNG: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ExportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ExportError::Io(err) => Some(err),
+            ExportError::Tiff(err) => Some(err),
+            ExportError::Png(err) => Some(err),
+        }
+    }
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RawFormat {
+    /// NumPy array with a structured dtype, one record per pixel.
+    Npy,
+    /// Multi-page TIFF with one page per field.
+    Tiff,
+    /// Consecutive arrays for each field, described by a JSON file next to it.
+    Binary,
+}
+
+impl RawFormat {
+    pub fn from_path(path: &Path) -> RawFormat {
+        match path.extension().and_then(|ext| ext.to_str()).map(|ext| ext.to_ascii_lowercase()).as_deref() {
+            Some("npy") => RawFormat::Npy,
+            Some("tif" | "tiff") => RawFormat::Tiff,
+            _ => RawFormat::Binary,
+        }
+    }
+}
+
+
+/// The exported values of a sample: iterations, smooth iterations and distance estimate.
+fn fields(sample: &Sample, max_iterations: u32) -> (u32, f32, f32) {
+    if sample.is_inside() {
+        (max_iterations, f32::NAN, 0.0)
+    } else {
+        (sample.depth, sample.smooth_depth(), sample.dist)
+    }
+}
+
+
+/// Writes the samples of an image of `location` in the format matching the file extension.
+pub fn write_raw(path: &Path, location: &Location, samples: &[Sample]) -> Result<(), ExportError> {
+    match RawFormat::from_path(path) {
+        RawFormat::Npy => write_npy(path, location, samples),
+        RawFormat::Tiff => write_tiff(path, location, samples),
+        RawFormat::Binary => write_binary(path, location, samples),
+    }
+}
+
+
+/// NPY format 1.0 header. The data has to start at a multiple of 64 bytes.
+fn npy_header(size: (u32, u32)) -> Vec<u8> {
+    let dict = format!(
+        "{{'descr': [('depth', '<u4'), ('smooth', '<f4'), ('dist_px', '<f4')], 'fortran_order': False, 'shape': ({}, {}), }}",
+        size.1, size.0
+    );
+    let unpadded = 10 + dict.len() + 1;
+    let padding = (64 - unpadded % 64) % 64;
+    let header_len = dict.len() + padding + 1;
+    let mut header = b"\x93NUMPY\x01\x00".to_vec();
+    header.extend_from_slice(&(header_len as u16).to_le_bytes());
+    header.extend_from_slice(dict.as_bytes());
+    header.extend(std::iter::repeat_n(b' ', padding));
+    header.push(b'\n');
+    header
+}
+
+/// NPY file written in chunks of complete image rows.
+pub struct NpyWriter {
+    writer: BufWriter<File>,
+    max_iterations: u32,
+}
+
+impl NpyWriter {
+    pub fn create(path: &Path, location: &Location) -> Result<NpyWriter, ExportError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(&npy_header(location.size))?;
+        Ok(NpyWriter{writer, max_iterations: location.fractal.max_iterations})
+    }
+
+    pub fn write(&mut self, samples: &[Sample]) -> Result<(), ExportError> {
+        for sample in samples {
+            let (depth, smooth, dist) = fields(sample, self.max_iterations);
+            self.writer.write_all(&depth.to_le_bytes())?;
+            self.writer.write_all(&smooth.to_le_bytes())?;
+            self.writer.write_all(&dist.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<(), ExportError> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+fn write_npy(path: &Path, location: &Location, samples: &[Sample]) -> Result<(), ExportError> {
+    let mut writer = NpyWriter::create(path, location)?;
+    writer.write(samples)?;
+    writer.finish()
+}
+
+
+fn write_tiff(path: &Path, location: &Location, samples: &[Sample]) -> Result<(), ExportError> {
+    let (size, max_iterations) = (location.size, location.fractal.max_iterations);
+    let mut encoder = TiffEncoder::new(BufWriter::new(File::create(path)?))?;
+    let depth: Vec<u32> = samples.iter().map(|sample| fields(sample, max_iterations).0).collect();
+    let smooth: Vec<f32> = samples.iter().map(|sample| fields(sample, max_iterations).1).collect();
+    let dist: Vec<f32> = samples.iter().map(|sample| fields(sample, max_iterations).2).collect();
+    encoder.write_image::<colortype::Gray32>(size.0, size.1, &depth)?;
+    encoder.write_image::<colortype::Gray32Float>(size.0, size.1, &smooth)?;
+    encoder.write_image::<colortype::Gray32Float>(size.0, size.1, &dist)?;
+    Ok(())
+}
+
+
+/// Path of the JSON description of a raw binary export.
+pub fn sidecar_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".json");
+    PathBuf::from(name)
+}
+
+fn sidecar_json(location: &Location) -> String {
+    let (size, view) = (location.size, location.view);
+    let array_bytes = size.0 as u64 * size.1 as u64 * 4;
+    format!(
+        concat!(
+            "{{\n",
+            "  \"width\": {},\n",
+            "  \"height\": {},\n",
+            "  \"center\": [{}, {}],\n",
+            "  \"scale\": {:e},\n",
+            "  \"rotation_deg\": {},\n",
+            "  \"formula\": \"{}\",\n",
+            "  \"max_iterations\": {},\n",
+            "  \"byte_order\": \"little\",\n",
+            "  \"row_order\": \"top_to_bottom\",\n",
+            "  \"fields\": [\n",
+            "    {{\"name\": \"depth\", \"dtype\": \"uint32\", \"offset\": 0}},\n",
+            "    {{\"name\": \"smooth\", \"dtype\": \"float32\", \"offset\": {}}},\n",
+            "    {{\"name\": \"dist_px\", \"dtype\": \"float32\", \"offset\": {}}}\n",
+            "  ]\n",
+            "}}\n"
+        ),
+        size.0, size.1, view.center.0, view.center.1, view.scale, view.angle.to_degrees(),
+        location.fractal.formula, location.fractal.max_iterations, array_bytes, 2 * array_bytes
+    )
+}
+
+fn write_binary(path: &Path, location: &Location, samples: &[Sample]) -> Result<(), ExportError> {
+    let max_iterations = location.fractal.max_iterations;
+    let mut writer = BufWriter::new(File::create(path)?);
+    for sample in samples {
+        writer.write_all(&fields(sample, max_iterations).0.to_le_bytes())?;
+    }
+    for sample in samples {
+        writer.write_all(&fields(sample, max_iterations).1.to_le_bytes())?;
+    }
+    for sample in samples {
+        writer.write_all(&fields(sample, max_iterations).2.to_le_bytes())?;
+    }
+    writer.flush()?;
+    std::fs::write(sidecar_path(path), sidecar_json(location))?;
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_npy_header_alignment() {
+        for size in [(1, 1), (1920, 1080), (40000, 40000)] {
+            let header = npy_header(size);
+            assert_eq!(header.len() % 64, 0);
+            assert_eq!(header.last(), Some(&b'\n'));
+            let header_len = u16::from_le_bytes([header[8], header[9]]) as usize;
+            assert_eq!(header.len(), 10 + header_len);
+        }
+    }
+
+    #[test]
+    fn test_raw_format_from_path() {
+        assert_eq!(RawFormat::from_path(Path::new("out.npy")), RawFormat::Npy);
+        assert_eq!(RawFormat::from_path(Path::new("out.TIFF")), RawFormat::Tiff);
+        assert_eq!(RawFormat::from_path(Path::new("out.bin")), RawFormat::Binary);
+        assert_eq!(sidecar_path(Path::new("dir/out.bin")), PathBuf::from("dir/out.bin.json"));
+    }
+
+    #[test]
+    fn test_inside_fields() {
+        let (depth, smooth, dist) = fields(&Sample::INSIDE, 500);
+        assert_eq!(depth, 500);
+        assert!(smooth.is_nan());
+        assert_eq!(dist, 0.0);
+    }
+}
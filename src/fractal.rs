@@ -0,0 +1,110 @@
+/// Fractal formulas and the iteration cap, shared by the CPU kernel and `res/mandelbrot.frag`.
+/// All formulas square a folded z, `z -> fold(z)^2 + c`, and differ only in the fold.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::kernel::Real;
+use crate::MAX_ITERATIONS;
+
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Formula {
+    #[default]
+    Mandelbrot,
+    /// Complex conjugate of z before squaring.
+    Tricorn,
+    /// Absolute values of the real and imaginary part before squaring.
+    BurningShip,
+}
+
+impl Formula {
+    pub const ALL: [Formula; 3] = [Formula::Mandelbrot, Formula::Tricorn, Formula::BurningShip];
+
+    pub fn next(&self) -> Formula {
+        Formula::ALL[(*self as usize + 1) % Formula::ALL.len()]
+    }
+
+    /// Folds z and its derivative z' before squaring, with the derivative following the fold of z.
+    #[inline(always)]
+    pub fn fold<T: Real>(&self, z: (T, T), z_prime: (T, T)) -> [T; 4] {
+        match self {
+            Formula::Mandelbrot => [z.0, z.1, z_prime.0, z_prime.1],
+            Formula::Tricorn => [z.0, -z.1, z_prime.0, -z_prime.1],
+            Formula::BurningShip => [z.0.abs(), z.1.abs(), z_prime.0.flip_signs(z.0), z_prime.1.flip_signs(z.1)],
+        }
+    }
+}
+
+impl fmt::Display for Formula {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Formula::Mandelbrot => write!(f, "mandelbrot"),
+            Formula::Tricorn => write!(f, "tricorn"),
+            Formula::BurningShip => write!(f, "burning-ship"),
+        }
+    }
+}
+
+impl FromStr for Formula {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Formula, String> {
+        Formula::ALL.into_iter()
+            .find(|formula| formula.to_string() == s)
+            .ok_or_else(|| format!("Expected 'mandelbrot', 'tricorn' or 'burning-ship', got '{}'", s))
+    }
+}
+
+
+/// What is iterated, as opposed to the view and the coloring.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fractal {
+    pub formula: Formula,
+    pub max_iterations: u32,
+}
+
+impl Fractal {
+    const ITERATION_RANGE: (u32, u32) = (25, 1 << 20);
+
+    /// Doubles the iteration cap, up to a limit that keeps a frame from taking minutes.
+    pub fn more_iterations(&mut self) {
+        self.max_iterations = (self.max_iterations * 2).min(Fractal::ITERATION_RANGE.1);
+    }
+
+    pub fn fewer_iterations(&mut self) {
+        self.max_iterations = (self.max_iterations / 2).max(Fractal::ITERATION_RANGE.0);
+    }
+}
+
+impl Default for Fractal {
+    fn default() -> Fractal {
+        Fractal{formula: Formula::default(), max_iterations: MAX_ITERATIONS}
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formula_parse() {
+        for formula in Formula::ALL {
+            assert_eq!(formula.to_string().parse(), Ok(formula));
+        }
+        assert_eq!(Formula::BurningShip.next(), Formula::Mandelbrot);
+        assert!("julia".parse::<Formula>().is_err());
+    }
+
+    #[test]
+    fn test_iteration_limits() {
+        let mut fractal = Fractal::default();
+        fractal.more_iterations();
+        assert_eq!(fractal.max_iterations, 2 * MAX_ITERATIONS);
+        for _ in 0..10 {
+            fractal.fewer_iterations();
+        }
+        assert_eq!(fractal.max_iterations, 25);
+    }
+}
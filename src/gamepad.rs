@@ -0,0 +1,182 @@
+/// Game controller input of the interactive modes.
+/// Buttons trigger the actions of `controls.rs`. The sticks and triggers move the view continuously,
+/// by how long they are held: the left stick pans, the right stick zooms (up and down) and rotates
+/// (left and right), and the triggers zoom out and in.
+
+use std::time::Duration;
+
+use sdl2::controller::{Axis, Button, GameController};
+use sdl2::event::Event;
+use sdl2::GameControllerSubsystem;
+
+use crate::controls::Action;
+use crate::view::View;
+use crate::FracFloat;
+
+
+/// Deflections below this fraction are treated as the stick resting.
+const DEADZONE: FracFloat = 0.15;
+/// Shorter window sides per second at full deflection.
+const PAN_SPEED: FracFloat = 1.0;
+/// Zoom factor per second at full deflection.
+const ZOOM_SPEED: FracFloat = 4.0;
+/// Radians per second at full deflection.
+const ROTATE_SPEED: FracFloat = std::f64::consts::FRAC_PI_2;
+/// Longest time step, so that the first frame after idling does not jump.
+const MAX_STEP: Duration = Duration::from_millis(50);
+
+
+/// Action of a controller button, the D-pad and face buttons adjust the fractal and its coloring.
+pub fn button_action(button: Button) -> Option<Action> {
+    match button {
+        Button::DPadUp => Some(Action::MoreIterations),
+        Button::DPadDown => Some(Action::FewerIterations),
+        Button::DPadLeft => Some(Action::PaletteBack),
+        Button::DPadRight => Some(Action::PaletteForward),
+        Button::A => Some(Action::NextColoring),
+        Button::B => Some(Action::NextColormap),
+        Button::X => Some(Action::NextFormula),
+        Button::Y => Some(Action::ResetView),
+        Button::LeftShoulder => Some(Action::RotateLeft),
+        Button::RightShoulder => Some(Action::RotateRight),
+        Button::Back => Some(Action::Screenshot),
+        Button::Start => Some(Action::Help),
+        _ => None,
+    }
+}
+
+
+/// Axis value in -1..1 with the deadzone removed.
+fn deflection(value: i16) -> FracFloat {
+    let v = (value as FracFloat / i16::MAX as FracFloat).clamp(-1.0, 1.0);
+    if v.abs() < DEADZONE {
+        0.0
+    } else {
+        (v - DEADZONE * v.signum()) / (1.0 - DEADZONE)
+    }
+}
+
+
+/// Stick and trigger positions, in -1..1 and 0..1 with the deadzone removed.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Sticks {
+    left: (FracFloat, FracFloat),
+    right: (FracFloat, FracFloat),
+    triggers: (FracFloat, FracFloat),
+}
+
+impl Sticks {
+    pub fn set_axis(&mut self, axis: Axis, value: i16) {
+        let v = deflection(value);
+        match axis {
+            Axis::LeftX => self.left.0 = v,
+            Axis::LeftY => self.left.1 = v,
+            Axis::RightX => self.right.0 = v,
+            Axis::RightY => self.right.1 = v,
+            Axis::TriggerLeft => self.triggers.0 = v,
+            Axis::TriggerRight => self.triggers.1 = v,
+        }
+    }
+
+    /// Whether the view keeps moving without further events.
+    pub fn is_active(&self) -> bool {
+        *self != Sticks::default()
+    }
+
+    /// Moves the view of a window of `size` pixels as if the sticks were held for `elapsed`.
+    /// Returns true if the view changed.
+    pub fn apply(&self, view: &mut View, size: (u32, u32), elapsed: Duration) -> bool {
+        if !self.is_active() {
+            return false;
+        }
+        let dt = elapsed.min(MAX_STEP).as_secs_f64() as FracFloat;
+        // Pushing the stick moves the view, so the image moves the other way
+        let pan = PAN_SPEED * u32::min(size.0, size.1) as FracFloat * dt;
+        view.pan(-self.left.0 * pan, -self.left.1 * pan);
+        // Stick y points down
+        view.zoom(ZOOM_SPEED.powf((self.triggers.1 - self.triggers.0 - self.right.1) * dt));
+        view.rotate(-self.right.0 * ROTATE_SPEED * dt);
+        dt > 0.0
+    }
+}
+
+
+pub struct Gamepads {
+    /// None if SDL could not initialize game controllers, no controllers are opened then.
+    subsystem: Option<GameControllerSubsystem>,
+    /// Controllers only send events while they are open.
+    open: Vec<GameController>,
+    pub sticks: Sticks,
+}
+
+impl Gamepads {
+    /// Controllers connected at startup are announced by `ControllerDeviceAdded` events as well.
+    pub fn new(subsystem: Option<GameControllerSubsystem>) -> Gamepads {
+        Gamepads{subsystem, open: Vec::new(), sticks: Sticks::default()}
+    }
+
+    /// Opens and closes controllers as they are connected and tracks their sticks.
+    pub fn handle_event(&mut self, event: &Event) {
+        match *event {
+            Event::ControllerDeviceAdded {which, ..} => {
+                let Some(subsystem) = &self.subsystem else { return };
+                match subsystem.open(which) {
+                    Ok(controller) => {
+                        println!("Connected controller {}.", controller.name());
+                        self.open.push(controller);
+                    },
+                    Err(err) => log::warn!("Failed to open controller {}: {}", which, err),
+                }
+            },
+            Event::ControllerDeviceRemoved {which, ..} => {
+                self.open.retain(|controller| controller.instance_id() != which);
+                // A controller removed while its stick was pushed would keep the view moving
+                self.sticks = Sticks::default();
+            },
+            Event::ControllerAxisMotion {axis, value, ..} => self.sticks.set_axis(axis, value),
+            _ => (),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deflection() {
+        assert_eq!(deflection(0), 0.0);
+        assert_eq!(deflection(3000), 0.0);
+        assert_eq!(deflection(i16::MAX), 1.0);
+        assert_eq!(deflection(i16::MIN), -1.0);
+        assert!(deflection(16000) > 0.0 && deflection(16000) < 0.5);
+    }
+
+    #[test]
+    fn test_sticks_move_view() {
+        let size = (800, 600);
+        let home = View::fit(size);
+        let mut sticks = Sticks::default();
+        let mut view = home;
+        assert!(!sticks.apply(&mut view, size, Duration::from_millis(16)));
+
+        sticks.set_axis(Axis::LeftX, i16::MAX);
+        sticks.set_axis(Axis::TriggerRight, i16::MAX);
+        assert!(sticks.is_active());
+        assert!(sticks.apply(&mut view, size, Duration::from_millis(16)));
+        assert!(view.center.0 > home.center.0);
+        assert!(view.scale < home.scale);
+
+        // Long frames are limited to a single step
+        let mut slow = home;
+        sticks.apply(&mut slow, size, Duration::from_secs(5));
+        let mut step = home;
+        sticks.apply(&mut step, size, MAX_STEP);
+        assert_eq!(slow, step);
+
+        sticks.set_axis(Axis::LeftX, 0);
+        sticks.set_axis(Axis::TriggerRight, 1000);
+        assert!(!sticks.is_active());
+    }
+}
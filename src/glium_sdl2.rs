@@ -148,6 +148,12 @@ impl SDL2Facade {
         self.backend.window_mut()
     }
 
+    /// Size of the window in window coordinates, which mouse events are reported in.
+    /// On HiDPI displays this is smaller than `get_framebuffer_dimensions`.
+    pub fn get_window_size(&self) -> (u32, u32) {
+        self.backend.window().size()
+    }
+
     /// Start drawing on the backbuffer.
     ///
     /// This function returns a `Frame`, which can be used to draw on it.
@@ -288,7 +294,12 @@ unsafe impl Backend for SDL2WindowBackend {
 
 
     fn resize(&self, new_size: (u32, u32)) {
-        self.window_mut().set_size(new_size.0, new_size.1).unwrap();
+        // The new size is in drawable pixels, the window size is set in window coordinates
+        let (drawable_w, drawable_h) = self.window().drawable_size();
+        let (window_w, window_h) = self.window().size();
+        let width = (new_size.0 as u64 * window_w as u64 / drawable_w.max(1) as u64) as u32;
+        let height = (new_size.1 as u64 * window_h as u64 / drawable_h.max(1) as u64) as u32;
+        self.window_mut().set_size(width, height).unwrap();
     }
 
 
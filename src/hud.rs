@@ -0,0 +1,169 @@
+/// On-screen text overlay drawn with glium.
+/// Glyphs come from a signed distance field atlas, so text stays sharp at any size
+/// and can be outlined or shadowed to stay legible over the fractal.
+
+use std::borrow::Cow;
+
+use glium::backend::Facade;
+use glium::texture::{ClientFormat, MipmapsOption, RawImage2d, Texture2d, UncompressedFloatFormat};
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter};
+use glium::{Blend, DrawParameters, Program, Surface, VertexBuffer};
+
+use crate::text_rendering::{AtlasKind, FontAtlas};
+
+
+#[derive(Copy, Clone)]
+struct TextVertex {
+    position: [f32; 2],
+    tex_coords: [f32; 2],
+}
+implement_vertex!(TextVertex, position, tex_coords);
+
+
+/// Appearance of drawn text. Colors are RGBA.
+#[derive(Clone, Copy, Debug)]
+pub struct TextStyle {
+    /// Height of the text in pixels.
+    pub size: f32,
+    pub color: [f32; 4],
+    pub outline_color: [f32; 4],
+    /// Outline thickness in pixels.
+    pub outline_width: f32,
+    pub shadow_color: [f32; 4],
+    /// Shadow displacement in pixels.
+    pub shadow_offset: (f32, f32),
+}
+
+impl TextStyle {
+    /// Style with all pixel dimensions multiplied by `factor`, e.g. to match the HiDPI pixel scale.
+    pub fn scaled(&self, factor: f32) -> TextStyle {
+        TextStyle {
+            size: self.size * factor,
+            outline_width: self.outline_width * factor,
+            shadow_offset: (self.shadow_offset.0 * factor, self.shadow_offset.1 * factor),
+            ..*self
+        }
+    }
+}
+
+impl Default for TextStyle {
+    fn default() -> TextStyle {
+        TextStyle {
+            size: 20.0,
+            color: [1.0, 1.0, 1.0, 1.0],
+            outline_color: [0.0, 0.0, 0.0, 1.0],
+            outline_width: 1.5,
+            shadow_color: [0.0, 0.0, 0.0, 0.5],
+            shadow_offset: (2.0, 2.0),
+        }
+    }
+}
+
+
+pub struct TextRenderer {
+    atlas: FontAtlas,
+    texture: Texture2d,
+    program: Program,
+}
+
+impl TextRenderer {
+    pub fn new<F: Facade>(gl: &F, atlas: FontAtlas) -> Result<TextRenderer, glium::ProgramCreationError> {
+        assert!(matches!(atlas.kind(), AtlasKind::SignedDistance { .. }), "Text renderer requires a distance field atlas");
+        let vert_shader = String::from_utf8_lossy(include_bytes!("../res/text.vert"));
+        let frag_shader = String::from_utf8_lossy(include_bytes!("../res/text.frag"));
+        let program = Program::from_source(gl, &vert_shader, &frag_shader, None)?;
+        let texture = upload_atlas(gl, &atlas);
+        Ok(TextRenderer { atlas, texture, program })
+    }
+
+    /// Draws `text` with its top left corner at `pos` in window pixels.
+    /// Newlines start a new line below `pos`.
+    pub fn draw<F: Facade, S: Surface>(&mut self, gl: &F, target: &mut S, text: &str, pos: (f32, f32), style: &TextStyle) {
+        let scale = style.size / self.atlas.size();
+        let ascent = self.atlas.ascent() * scale;
+        let line_height = self.atlas.line_height() * scale;
+        let mut vertices = Vec::with_capacity(6 * text.len());
+
+        // Missing glyphs are added first, adding glyphs can grow the atlas and change texture coordinates
+        for c in text.chars().filter(|&c| c != '\n') {
+            let _ = self.atlas.glyph(c);
+        }
+        if self.atlas.take_dirty() {
+            self.texture = upload_atlas(gl, &self.atlas);
+        }
+        let atlas_size = self.atlas.canvas().size.to_f32();
+
+        let mut pen = (pos.0, pos.1 + ascent);
+        for c in text.chars() {
+            if c == '\n' {
+                pen = (pos.0, pen.1 + line_height);
+                continue;
+            }
+            let Ok(glyph) = self.atlas.glyph(c) else { continue };
+            if glyph.rect.width() > 0 {
+                let x0 = pen.0 + glyph.bearing.x() as f32 * scale;
+                let y0 = pen.1 + glyph.bearing.y() as f32 * scale;
+                let x1 = x0 + glyph.rect.width() as f32 * scale;
+                let y1 = y0 + glyph.rect.height() as f32 * scale;
+                let u0 = glyph.rect.min_x() as f32 / atlas_size.x();
+                let v0 = glyph.rect.min_y() as f32 / atlas_size.y();
+                let u1 = glyph.rect.max_x() as f32 / atlas_size.x();
+                let v1 = glyph.rect.max_y() as f32 / atlas_size.y();
+                vertices.extend_from_slice(&[
+                    TextVertex { position: [x0, y0], tex_coords: [u0, v0] },
+                    TextVertex { position: [x1, y0], tex_coords: [u1, v0] },
+                    TextVertex { position: [x0, y1], tex_coords: [u0, v1] },
+                    TextVertex { position: [x1, y0], tex_coords: [u1, v0] },
+                    TextVertex { position: [x1, y1], tex_coords: [u1, v1] },
+                    TextVertex { position: [x0, y1], tex_coords: [u0, v1] },
+                ]);
+            }
+            pen.0 += glyph.advance * scale;
+        }
+        if vertices.is_empty() {
+            return;
+        }
+
+        let AtlasKind::SignedDistance { spread } = self.atlas.kind() else { unreachable!() };
+        // Distance field units per screen pixel
+        let field_per_px = 0.5 / (spread * scale);
+        let (w, h) = target.get_dimensions();
+        let vbo = VertexBuffer::new(gl, &vertices).unwrap();
+        let params = DrawParameters {
+            blend: Blend::alpha_blending(),
+            ..Default::default()
+        };
+        target.draw(
+            &vbo,
+            glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList),
+            &self.program,
+            &uniform!{
+                window_size: (w as f32, h as f32),
+                atlas: self.texture.sampled()
+                    .magnify_filter(MagnifySamplerFilter::Linear)
+                    .minify_filter(MinifySamplerFilter::Linear),
+                text_color: style.color,
+                outline_color: style.outline_color,
+                outline_width: (style.outline_width * field_per_px).min(0.45),
+                shadow_color: style.shadow_color,
+                shadow_offset: (
+                    style.shadow_offset.0 / scale / atlas_size.x(),
+                    style.shadow_offset.1 / scale / atlas_size.y(),
+                ),
+            },
+            &params
+        ).unwrap();
+    }
+}
+
+
+fn upload_atlas<F: Facade>(gl: &F, atlas: &FontAtlas) -> Texture2d {
+    let canvas = atlas.canvas();
+    let image = RawImage2d {
+        data: Cow::Borrowed(&canvas.pixels[..]),
+        width: canvas.size.x() as u32,
+        height: canvas.size.y() as u32,
+        format: ClientFormat::U8,
+    };
+    Texture2d::with_format(gl, image, UncompressedFloatFormat::U8, MipmapsOption::NoMipmap).unwrap()
+}
@@ -0,0 +1,278 @@
+/// Fractal iteration of a batch of points per call, and of single points for the orbit overlay.
+/// Batches are SIMD vectors of the `wide` crate, `f64x4` or `f64x8`, stepped with the same generic code as
+/// single points. Escaped lanes are masked out with a select, so that they keep their final values while the
+/// remaining ones continue. `wide` uses AVX or AVX-512 when the target has them, SSE2 or scalar code otherwise.
+
+use std::ops::{Add, Mul, Neg, Sub};
+
+use wide::{f64x4, f64x8};
+
+use crate::fractal::{Formula, Fractal};
+use crate::FracFloat;
+
+
+/// Number of points per `escape_lanes` call.
+pub const LANES: usize = 8;
+
+
+/// Arithmetic of single points and of vectors of them, so that both iterate with the same code.
+pub trait Real: Copy + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self> + Neg<Output = Self> {
+    fn splat(value: FracFloat) -> Self;
+    fn abs(self) -> Self;
+    /// `self` with its sign flipped where `sign` is negative, including -0.
+    fn flip_signs(self, sign: Self) -> Self;
+}
+
+impl Real for FracFloat {
+    #[inline(always)]
+    fn splat(value: FracFloat) -> Self {
+        value
+    }
+
+    #[inline(always)]
+    fn abs(self) -> Self {
+        FracFloat::abs(self)
+    }
+
+    #[inline(always)]
+    fn flip_signs(self, sign: Self) -> Self {
+        self * FracFloat::copysign(1.0, sign)
+    }
+}
+
+
+/// Vectors of `N` points, with masks in which the lanes are either all ones or all zeros.
+pub trait Lanes<const N: usize>: Real {
+    fn from_array(values: [FracFloat; N]) -> Self;
+    fn to_array(self) -> [FracFloat; N];
+    /// Mask of the lanes where `self < other`, false for NaN.
+    fn lt(self, other: Self) -> Self;
+    fn and(self, other: Self) -> Self;
+    fn any(self) -> bool;
+    /// `if_true` in the lanes of the mask `self`, `if_false` in the others.
+    fn select(self, if_true: Self, if_false: Self) -> Self;
+}
+
+macro_rules! impl_lanes {
+    ($($vector:ident: $n:literal),*) => {$(
+        impl Real for $vector {
+            #[inline(always)]
+            fn splat(value: FracFloat) -> Self {
+                $vector::splat(value)
+            }
+
+            #[inline(always)]
+            fn abs(self) -> Self {
+                $vector::abs(self)
+            }
+
+            #[inline(always)]
+            fn flip_signs(self, sign: Self) -> Self {
+                $vector::flip_signs(self, sign)
+            }
+        }
+
+        impl Lanes<$n> for $vector {
+            #[inline(always)]
+            fn from_array(values: [FracFloat; $n]) -> Self {
+                $vector::new(values)
+            }
+
+            #[inline(always)]
+            fn to_array(self) -> [FracFloat; $n] {
+                $vector::to_array(self)
+            }
+
+            #[inline(always)]
+            fn lt(self, other: Self) -> Self {
+                $vector::simd_lt(self, other)
+            }
+
+            #[inline(always)]
+            fn and(self, other: Self) -> Self {
+                self & other
+            }
+
+            #[inline(always)]
+            fn any(self) -> bool {
+                $vector::any(self)
+            }
+
+            #[inline(always)]
+            fn select(self, if_true: Self, if_false: Self) -> Self {
+                wide::Select::select(self, if_true, if_false)
+            }
+        }
+    )*};
+}
+
+impl_lanes!(f64x4: 4, f64x8: 8);
+
+
+/// Final state of a point that escaped.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Escape {
+    pub depth: u32,
+    /// First z outside of the escape radius.
+    pub z: (FracFloat, FracFloat),
+    /// Distance estimate to the set.
+    pub dist: FracFloat,
+}
+
+impl Escape {
+    fn new(depth: u32, z: (FracFloat, FracFloat), z_prime: (FracFloat, FracFloat)) -> Escape {
+        let z_mag = (z.0*z.0 + z.1*z.1).sqrt();
+        let z_prime_mag = (z_prime.0*z_prime.0 + z_prime.1*z_prime.1).sqrt();
+        Escape{depth, z, dist: z_mag*z_mag.ln()/z_prime_mag}
+    }
+}
+
+
+/// One iteration `z -> fold(z)^2 + c` of `formula`, along with the derivative z' by c.
+#[inline(always)]
+fn step<T: Real>(formula: Formula, z: (T, T), z_prime: (T, T), c: (T, T)) -> ((T, T), (T, T)) {
+    let [wr, wi, wpr, wpi] = formula.fold(z, z_prime);
+    let (one, two) = (T::splat(1.0), T::splat(2.0));
+    ((wr*wr - wi*wi + c.0, two*wr*wi + c.1), (two*(wr*wpr - wi*wpi) + one, two*(wr*wpi + wi*wpr)))
+}
+
+
+/// Iterates a single point of `fractal`, the scalar counterpart of `escape_lanes`.
+pub fn escape(fractal: &Fractal, c: (FracFloat, FracFloat)) -> Option<Escape> {
+    escape_visiting(fractal, c, |_| true)
+}
+
+
+/// Like `escape`, passing the iterates z1, z2, ... up to the first one outside the escape radius to `visit`.
+/// The iteration stops early, as if the point did not escape, once `visit` returns false.
+pub fn escape_visiting(fractal: &Fractal, c: (FracFloat, FracFloat), mut visit: impl FnMut((FracFloat, FracFloat)) -> bool) -> Option<Escape> {
+    let (mut z, mut z_prime) = ((0.0, 0.0), (1.0, 0.0));
+    for depth in 1..=fractal.max_iterations {
+        (z, z_prime) = step(fractal.formula, z, z_prime, c);
+        if !visit(z) {
+            return None;
+        }
+        // Also stops on NaN, like the lanes
+        let inside = z.0*z.0 + z.1*z.1 < 4.0;
+        if !inside {
+            return (depth < fractal.max_iterations).then(|| Escape::new(depth, z, z_prime));
+        }
+    }
+    None
+}
+
+
+/// The iterates z0 = 0, z1 = c, ... of `c` up to the first one outside the escape radius,
+/// or at most the iteration cap and `limit` points.
+pub fn orbit(fractal: &Fractal, c: (FracFloat, FracFloat), limit: usize) -> Vec<(FracFloat, FracFloat)> {
+    let mut orbit = vec![(0.0, 0.0)];
+    escape_visiting(fractal, c, |z| {
+        let room = orbit.len() < limit;
+        if room {
+            orbit.push(z);
+        }
+        room
+    });
+    orbit
+}
+
+
+/// Iterates the `N` points of a vector `V` of `fractal` together, with the same results as `escape` for each.
+/// Escaped lanes are masked out by selecting their previous values, so that they keep their final state.
+pub fn escape_vector<V: Lanes<N>, const N: usize>(fractal: &Fractal, c_real: &[FracFloat; N], c_imag: &[FracFloat; N])
+    -> [Option<Escape>; N]
+{
+    let c = (V::from_array(*c_real), V::from_array(*c_imag));
+    let (zero, one, radius) = (V::splat(0.0), V::splat(1.0), V::splat(4.0));
+    let (mut z, mut z_prime) = ((zero, zero), (one, zero));
+    let mut depth = zero;
+    let mut active = zero.lt(one);
+
+    let mut i = 0;
+    while i < fractal.max_iterations && active.any() {
+        let (next, next_prime) = step(fractal.formula, z, z_prime, c);
+        z = (active.select(next.0, z.0), active.select(next.1, z.1));
+        z_prime = (active.select(next_prime.0, z_prime.0), active.select(next_prime.1, z_prime.1));
+        depth = depth + active.select(one, zero);
+        active = active.and((next.0*next.0 + next.1*next.1).lt(radius));
+        i += 1;
+    }
+
+    let depth = depth.to_array();
+    let (z_real, z_imag) = (z.0.to_array(), z.1.to_array());
+    let (z_prime_real, z_prime_imag) = (z_prime.0.to_array(), z_prime.1.to_array());
+    std::array::from_fn(|lane| {
+        let depth = depth[lane] as u32;
+        (depth < fractal.max_iterations)
+            .then(|| Escape::new(depth, (z_real[lane], z_imag[lane]), (z_prime_real[lane], z_prime_imag[lane])))
+    })
+}
+
+
+/// Iterates `LANES` points of `fractal` in one vector, the batch used by the CPU renderers.
+pub fn escape_lanes(fractal: &Fractal, c_real: &[FracFloat; LANES], c_imag: &[FracFloat; LANES]) -> [Option<Escape>; LANES] {
+    escape_vector::<f64x8, LANES>(fractal, c_real, c_imag)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mandelbrot_depth;
+
+    fn assert_vector_matches_scalar<V: Lanes<N>, const N: usize>(fractal: &Fractal) {
+        for y in -20..20 {
+            for x in (-40..24).step_by(N) {
+                let c_real: [FracFloat; N] = std::array::from_fn(|lane| (x + lane as i32) as FracFloat * 0.05);
+                let c_imag = [y as FracFloat * 0.05 + 0.01; N];
+                let results = escape_vector::<V, N>(fractal, &c_real, &c_imag);
+                for lane in 0..N {
+                    assert_eq!(results[lane], escape(fractal, (c_real[lane], c_imag[lane])));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_lanes_match_scalar() {
+        for formula in Formula::ALL {
+            let fractal = Fractal{formula, ..Default::default()};
+            assert_vector_matches_scalar::<f64x4, 4>(&fractal);
+            assert_vector_matches_scalar::<f64x8, 8>(&fractal);
+        }
+        for y in -20..20 {
+            let c = (-1.3, y as FracFloat * 0.05 + 0.01);
+            let escape = escape(&Fractal::default(), c).map(|escape| (escape.depth, escape.dist));
+            assert_eq!(escape, mandelbrot_depth(c.0, c.1));
+        }
+    }
+
+    #[test]
+    fn test_formulas_and_iteration_cap() {
+        let fractal = |formula| Fractal{formula, ..Default::default()};
+        // z stays real on the real axis, where conjugating it changes nothing
+        let c_real = [-1.75, -1.2, 0.3, -0.1, -1.9, -0.5, 0.2, -1.4];
+        let escapes = |formula, c_imag| escape_lanes(&fractal(formula), &c_real, &[c_imag; LANES]).map(|e| e.map(|e| e.depth));
+        assert_eq!(escapes(Formula::Mandelbrot, 0.0), escapes(Formula::Tricorn, 0.0));
+        assert_ne!(escapes(Formula::Mandelbrot, 0.6), escapes(Formula::BurningShip, 0.6));
+        let scalar = c_real.map(|c_real| escape(&fractal(Formula::BurningShip), (c_real, 0.6)).map(|e| e.depth));
+        assert_eq!(scalar, escapes(Formula::BurningShip, 0.6));
+        // Points of the set do not escape with a lower cap either
+        let short = Fractal{max_iterations: 10, ..Default::default()};
+        assert!(escape_lanes(&short, &[0.0; LANES], &[0.0; LANES]).iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn test_orbit() {
+        let fractal = Fractal::default();
+        for c in [(0.5, 0.5), (-0.75, 0.15), (1.0, 1.0)] {
+            let (depth, _) = mandelbrot_depth(c.0, c.1).unwrap();
+            let orbit = orbit(&fractal, c, usize::MAX);
+            assert_eq!(orbit.len(), depth as usize + 1);
+            assert_eq!(orbit[1], c);
+            assert!(orbit[depth as usize].0.hypot(orbit[depth as usize].1) >= 2.0);
+        }
+        assert_eq!(orbit(&fractal, (0.0, 0.0), usize::MAX).len(), fractal.max_iterations as usize + 1);
+        assert_eq!(orbit(&fractal, (0.0, 0.0), 10).len(), 10);
+    }
+}
@@ -0,0 +1,207 @@
+/// Parameters that reproduce a render, stored as text chunks in PNG output and loaded back with `--location`.
+/// Location files hold the same entries as `Key: value` lines.
+
+use std::f64::consts::TAU;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+use std::str::FromStr;
+
+use png::DecodingError;
+
+use crate::coloring::Coloring;
+use crate::fractal::Fractal;
+use crate::view::{Center, View, WindowSize};
+use crate::FracFloat;
+
+
+const SOFTWARE: &str = concat!("latcarf ", env!("CARGO_PKG_VERSION"));
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+
+
+#[derive(Debug)]
+pub enum LocationError {
+    Io(io::Error),
+    Png(DecodingError),
+    Invalid(String),
+}
+
+impl From<io::Error> for LocationError {
+    fn from(err: io::Error) -> LocationError {
+        LocationError::Io(err)
+    }
+}
+
+impl From<DecodingError> for LocationError {
+    fn from(err: DecodingError) -> LocationError {
+        LocationError::Png(err)
+    }
+}
+
+impl fmt::Display for LocationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LocationError::Io(err) => write!(f, "Failed to read location: {}", err),
+            LocationError::Png(err) => write!(f, "Failed to read location from PNG: {}", err),
+            LocationError::Invalid(reason) => write!(f, "Invalid location: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for LocationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LocationError::Io(err) => Some(err),
+            LocationError::Png(err) => Some(err),
+            LocationError::Invalid(_) => None,
+        }
+    }
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Location {
+    pub view: View,
+    /// Image size the view was rendered at.
+    pub size: (u32, u32),
+    pub fractal: Fractal,
+    pub coloring: Coloring,
+}
+
+impl Location {
+    /// The view for an image of `size` pixels, showing the same extent along the shorter side.
+    pub fn view(&self, size: (u32, u32)) -> View {
+        let mut view = self.view;
+        view.resize(self.size, size);
+        view
+    }
+
+    pub fn resize(&mut self, size: (u32, u32)) {
+        self.view = self.view(size);
+        self.size = size;
+    }
+
+    /// Keys and values as stored in PNG text chunks.
+    pub fn entries(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("Center", Center{re: self.view.center.0, im: self.view.center.1}.to_string()),
+            ("Scale", self.view.scale.to_string()),
+            // Radians, which unlike degrees load back bit for bit
+            ("Angle", self.view.angle.to_string()),
+            ("Size", WindowSize{width: self.size.0, height: self.size.1}.to_string()),
+            ("Formula", self.fractal.formula.to_string()),
+            ("Iterations", self.fractal.max_iterations.to_string()),
+            ("Coloring", self.coloring.mode.to_string()),
+            ("Colormap", self.coloring.colormap.to_string()),
+            ("Palette offset", self.coloring.offset.to_string()),
+            ("Software", SOFTWARE.to_string()),
+        ]
+    }
+
+    /// Location from key-value pairs. Unknown keys, like those of other software, are skipped.
+    pub fn from_entries<'a>(entries: impl IntoIterator<Item = (&'a str, &'a str)>) -> Result<Location, LocationError> {
+        let mut center = None;
+        let mut scale = None;
+        let mut size = None;
+        let mut angle = 0.0;
+        let mut fractal = Fractal::default();
+        let mut coloring = Coloring::default();
+        for (key, value) in entries {
+            let value = value.trim();
+            match key.trim() {
+                "Center" => center = Some(parse::<Center>(key, value)?),
+                "Scale" => scale = Some(parse::<FracFloat>(key, value)?).filter(|scale| *scale > 0.0),
+                "Angle" => angle = parse::<FracFloat>(key, value)?.rem_euclid(TAU),
+                "Size" => size = Some(parse::<WindowSize>(key, value)?),
+                "Formula" => fractal.formula = parse(key, value)?,
+                "Iterations" => fractal.max_iterations = parse::<u32>(key, value)?.max(1),
+                "Coloring" => coloring.mode = parse(key, value)?,
+                "Colormap" => coloring.colormap = parse(key, value)?,
+                "Palette offset" => coloring.offset = parse::<f32>(key, value)?.rem_euclid(1.0),
+                _ => (),
+            }
+        }
+        let missing = |key: &str| LocationError::Invalid(format!("missing or invalid '{}'", key));
+        let center = center.ok_or_else(|| missing("Center"))?;
+        let size = size.ok_or_else(|| missing("Size"))?;
+        Ok(Location{
+            view: View{center: (center.re, center.im), scale: scale.ok_or_else(|| missing("Scale"))?, angle},
+            size: (size.width, size.height),
+            fractal,
+            coloring,
+        })
+    }
+
+    /// Reads the text chunks of a PNG written by latcarf, or a location file.
+    pub fn load(path: &Path) -> Result<Location, LocationError> {
+        let mut reader = BufReader::new(File::open(path)?);
+        if reader.fill_buf()?.starts_with(&PNG_SIGNATURE) {
+            let png = png::Decoder::new(reader).read_info()?;
+            let chunks = &png.info().uncompressed_latin1_text;
+            Location::from_entries(chunks.iter().map(|chunk| (chunk.keyword.as_str(), chunk.text.as_str())))
+        } else {
+            let mut text = String::new();
+            reader.read_to_string(&mut text)?;
+            text.parse()
+        }
+    }
+}
+
+fn parse<T: FromStr>(key: &str, value: &str) -> Result<T, LocationError> {
+    value.parse().map_err(|_| LocationError::Invalid(format!("'{}' is not a valid {}", value, key)))
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (key, value) in self.entries() {
+            writeln!(f, "{}: {}", key, value)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Location {
+    type Err = LocationError;
+
+    fn from_str(s: &str) -> Result<Location, LocationError> {
+        Location::from_entries(s.lines().filter_map(|line| line.split_once(':')))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coloring::{ColorMode, Colormap};
+    use crate::fractal::Formula;
+
+    #[test]
+    fn test_location_roundtrip() {
+        let coloring = Coloring{mode: ColorMode::Smooth, colormap: Colormap::Pastel1, offset: 0.3};
+        let location = Location{
+            view: View{center: (-1.985424253, 1e-20), scale: 3.7e-13, angle: 1.234567},
+            size: (4000, 3000),
+            fractal: Fractal{formula: Formula::BurningShip, max_iterations: 5000},
+            coloring,
+        };
+        assert_eq!(location.to_string().parse::<Location>().unwrap(), location);
+        let entries = location.entries();
+        let pairs = entries.iter().map(|(key, value)| (*key, value.as_str()));
+        assert_eq!(Location::from_entries(pairs).unwrap(), location);
+        for i in 0..100 {
+            let rotated = Location{view: View{angle: i as FracFloat * 0.0627, ..location.view}, ..location};
+            assert_eq!(rotated.to_string().parse::<Location>().unwrap(), rotated);
+        }
+    }
+
+    #[test]
+    fn test_location_errors() {
+        assert!("Center: 0,0\nScale: 0.01".parse::<Location>().is_err());
+        assert!("Center: 0,0\nScale: 0.01\nSize: 10x10\nFormula: julia".parse::<Location>().is_err());
+        let location: Location = "Center: 0,0\nScale: 0.01\nSize: 10x10\nComment: from elsewhere".parse().unwrap();
+        assert_eq!(location.coloring, Coloring::default());
+        // Same extent along the shorter side at a different size
+        assert_eq!(location.view((40, 20)).scale, 0.005);
+    }
+}
@@ -1,22 +1,64 @@
+mod bench;
+mod camera;
+mod coloring;
+mod controls;
+mod error;
+mod export;
+mod fractal;
+mod gamepad;
 mod glium_sdl2;
+mod hud;
+mod kernel;
+mod location;
+mod orbit;
+mod packer;
+mod pan;
+mod pacing;
+mod progressive;
+mod render;
+mod screenshot;
+mod stats;
+mod subdivide;
 mod text_rendering;
+mod tiles;
+mod touch;
+mod view;
 
-use image::{ImageBuffer, Rgb};
 use sdl2::event::Event;
-use sdl2::pixels::Color;
-use sdl2::render::{Canvas, RenderTarget};
 use sdl2::gfx::primitives::DrawRenderer;
-use glium::VertexBuffer;
-use glium::Surface;
-use clap::Parser;
-use itertools::Itertools;
+use sdl2::mouse::MouseButton;
+use sdl2::render::Canvas;
+use sdl2::video::{FullscreenType, Window};
+use sdl2::pixels::{Color, PixelFormatEnum};
+use glium::{DrawParameters, Surface, VertexBuffer};
+use glium::backend::Facade;
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::texture::{MipmapsOption, Texture2d, UncompressedFloatFormat};
+use clap::{Parser, Subcommand};
+use std::collections::BTreeSet;
 use std::time::{Duration, Instant};
-use std::collections::VecDeque;
+use std::path::PathBuf;
 use std::process;
-use env_logger;
 
+use crate::bench::BenchArgs;
+use crate::camera::Camera;
+use crate::coloring::{ColorMode, Colormap, Coloring};
+use crate::controls::{Action, Binding, Bookmarks, Controls};
+use crate::error::AppError;
+use crate::export::ExportError;
+use crate::fractal::{Formula, Fractal};
+use crate::gamepad::Gamepads;
 use crate::glium_sdl2::DisplayBuild;
-use crate::text_rendering::{load_default_fonts, generate_atlas};
+use crate::hud::{TextRenderer, TextStyle};
+use crate::location::Location;
+use crate::orbit::OrbitOverlay;
+use crate::pacing::{FramePacer, FrameMode};
+use crate::progressive::CpuStrategy;
+use crate::render::RenderArgs;
+use crate::stats::{FrameStats, GpuTimer, StatsLog};
+use crate::text_rendering::{load_fonts, AtlasKind, CharSet, FontAtlas};
+use crate::touch::Touch;
+use crate::view::{PixelScale, View, WindowSize};
 
 #[macro_use]
 extern crate glium;
@@ -26,8 +68,90 @@ extern crate glium;
 #[command(version, about, long_about = None)]
 struct Args {
     device: Option<String>,
+    /// Debug logging, also writes the font atlas to font_atlas.png
     #[arg(short, long)]
     debug: bool,
+    /// Font for on-screen text, either an installed family name or a path to a TTF/OTF file
+    #[arg(long)]
+    font: Option<String>,
+    /// Characters to prerender into the font atlas, e.g. "U+20-U+7E,α-ω,×≈", with code points written as U+hex
+    #[arg(long, default_value_t)]
+    charset: CharSet,
+    /// Initial window size
+    #[arg(long, default_value_t)]
+    size: WindowSize,
+    /// Start in fullscreen mode, toggle with F11
+    #[arg(long)]
+    fullscreen: bool,
+    /// Frame pacing: "vsync", "adaptive", "uncapped" or a frame rate limit
+    #[arg(long, default_value_t)]
+    pacing: FrameMode,
+    /// Start at the location stored in a PNG rendered by latcarf, or in a location file
+    #[arg(long, global = true)]
+    location: Option<PathBuf>,
+    /// Iterated formula: "mandelbrot" (default), "tricorn" or "burning-ship"
+    #[arg(long, global = true)]
+    formula: Option<Formula>,
+    /// Iteration cap, 200 by default
+    #[arg(long, global = true)]
+    iterations: Option<u32>,
+    /// Coloring of the iteration results: "distance" (default), "smooth" or "edges"
+    #[arg(long, global = true)]
+    coloring: Option<ColorMode>,
+    /// Colormap: "waves" (default) or "pastel1"
+    #[arg(long, global = true)]
+    colormap: Option<Colormap>,
+    /// Shift along the colormap, 0 (default) to 1
+    #[arg(long, global = true)]
+    palette_offset: Option<f32>,
+    /// CPU rendering strategy: "progressive" or "subdivide"
+    #[arg(long, global = true, default_value_t)]
+    strategy: CpuStrategy,
+    /// Bind a key to an action, e.g. "zoom-in=E", replacing the default keys of the action
+    #[arg(long, value_name = "ACTION=KEY")]
+    bind: Vec<Binding>,
+    /// Location for the number keys 1-9, in order, like `--location`
+    #[arg(long)]
+    bookmark: Vec<PathBuf>,
+    /// Write per-frame CPU and GPU times to a CSV file
+    #[arg(long)]
+    stats: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Render a fixed set of views offscreen with every backend and report the throughput
+    Bench(BenchArgs),
+    /// Render a single image offscreen and write it to files
+    Render(RenderArgs),
+}
+
+impl Args {
+    /// The location given with `--location`, or the full view at `size`,
+    /// with the fractal and coloring options given on the command line taking precedence.
+    fn initial_location(&self, size: (u32, u32)) -> Result<Location, AppError> {
+        let mut location = match &self.location {
+            Some(path) => Location::load(path)?,
+            None => Location{view: View::fit(size), size, fractal: Fractal::default(), coloring: Coloring::default()},
+        };
+        let fractal = &mut location.fractal;
+        fractal.formula = self.formula.unwrap_or(fractal.formula);
+        fractal.max_iterations = self.iterations.map_or(fractal.max_iterations, |iterations| iterations.max(1));
+        let coloring = &mut location.coloring;
+        coloring.mode = self.coloring.unwrap_or(coloring.mode);
+        coloring.colormap = self.colormap.unwrap_or(coloring.colormap);
+        coloring.offset = self.palette_offset.map_or(coloring.offset, |offset| offset.rem_euclid(1.0));
+        Ok(location)
+    }
+
+    fn bookmarks(&self) -> Result<Bookmarks, AppError> {
+        let locations = self.bookmark.iter()
+            .map(|path| Location::load(path))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Bookmarks::new(locations))
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -39,10 +163,13 @@ implement_vertex!(Vertex, position);
 
 type FracFloat = f64;
 const MAX_ITERATIONS: u32 = 200;
+/// Number of frames that timing statistics are computed over.
+const STATS_WINDOW: usize = 120;
+/// Time spent refining a progressive CPU render before the intermediate result is shown.
+const REFINE_BUDGET: Duration = Duration::from_millis(25);
 
 
 fn main() {
-    env_logger::init();
     let cli = Args::parse();
 
     // Prepare logging
@@ -53,61 +180,150 @@ fn main() {
     log_builder.init();
 
     // Pick device to run on
-    if let Some(device) = cli.device {
-        if device == "cpu" {
-            cpu_mode();
-        } else if device == "gpu" {
-            gpu_mode();
-        } else {
-            println!("Invalid device '{}'", device);
-        }
-    } else {
-        gpu_mode();
+    let result = match (&cli.command, cli.device.as_deref()) {
+        (Some(Command::Bench(args)), _) => bench::run(&cli, args),
+        (Some(Command::Render(args)), _) => render::run(&cli, args),
+        (None, Some("cpu")) => cpu_mode(&cli),
+        (None, Some("gpu") | None) => match gpu_mode(&cli) {
+            Err(err) if err.is_gl_unavailable() => {
+                eprintln!("{}", err);
+                eprintln!("Falling back to CPU rendering.");
+                cpu_mode(&cli)
+            },
+            result => result,
+        },
+        (None, Some(device)) => Err(AppError::InvalidDevice(device.to_string())),
+    };
+    if let Err(err) = result {
+        eprintln!("Error: {}", err);
+        process::exit(1);
     }
 }
 
 
-fn print_glsl_error(err: glium::ProgramCreationError, frag_shader: &str, vert_shader: &str) {
-    println!("Failed to compile shader:");
-    if let glium::CompilationError(compile_err, shader_type) = err {
-        let line_number: usize = (&compile_err)
-            .split(":").nth(1).unwrap()
-            .split("(").nth(0).unwrap()
-            .parse().unwrap();
-        print!("{}", &compile_err);
-        println!("In line {line_number}:");
-        match shader_type {
-            glium::program::ShaderType::Fragment => println!("{}", frag_shader.split("\n").nth(line_number-1).unwrap()),
-            glium::program::ShaderType::Vertex => println!("{}", vert_shader.split("\n").nth(line_number-1).unwrap()),
-            _ => (),
+fn format_glsl_error(err: glium::ProgramCreationError, frag_shader: &str, vert_shader: &str) -> String {
+    if let glium::CompilationError(compile_err, shader_type) = &err {
+        let line_number: Option<usize> = compile_err
+            .split(":").nth(1)
+            .and_then(|s| s.split("(").nth(0))
+            .and_then(|s| s.trim().parse().ok());
+        let source = match shader_type {
+            glium::program::ShaderType::Fragment => Some(frag_shader),
+            glium::program::ShaderType::Vertex => Some(vert_shader),
+            _ => None,
+        };
+        let line = line_number.zip(source).and_then(|(n, source)| Some((n, source.split("\n").nth(n.checked_sub(1)?)?)));
+        match line {
+            Some((line_number, line)) => format!("{}In line {line_number}:\n{}", compile_err, line),
+            None => compile_err.clone(),
         }
     } else {
-        println!("{}", err);
+        err.to_string()
+    }
+}
+
+
+/// Builder for the main window as configured on the command line.
+fn window_builder(video_subsystem: &sdl2::VideoSubsystem, cli: &Args) -> sdl2::video::WindowBuilder {
+    let mut builder = video_subsystem.window("latcarf", cli.size.width, cli.size.height);
+    builder.position_centered().resizable().allow_highdpi();
+    if cli.fullscreen {
+        builder.fullscreen_desktop();
     }
+    builder
 }
 
 
-fn gpu_mode() {
-    let sdl_context = sdl2::init().unwrap();
-    let video_subsystem = sdl_context.video().unwrap();
+fn toggle_fullscreen(window: &mut Window) -> Result<(), AppError> {
+    let next = match window.fullscreen_state() {
+        FullscreenType::Off => FullscreenType::Desktop,
+        _ => FullscreenType::Off,
+    };
+    window.set_fullscreen(next).map_err(AppError::Sdl)
+}
+
+
+/// Applies mouse navigation to the view, dragging with RMB rotates around the center of the window of `size` pixels.
+fn navigate(view: &mut View, pixel_scale: &PixelScale, size: (u32, u32), event: &Event) {
+    match *event {
+        Event::MouseWheel {precise_y, ..} => view.zoom((0.1 * precise_y as FracFloat).exp()),
+        Event::MouseMotion {mousestate, xrel, yrel, ..} if mousestate.left() => {
+            let (dx, dy) = pixel_scale.to_pixels(xrel as FracFloat, yrel as FracFloat);
+            view.pan(dx, dy)
+        },
+        Event::MouseMotion {mousestate, x, y, xrel, yrel, ..} if mousestate.right() => {
+            let (x, y) = pixel_scale.to_pixels(x as FracFloat, y as FracFloat);
+            let (dx, dy) = pixel_scale.to_pixels(xrel as FracFloat, yrel as FracFloat);
+            let (cx, cy) = (0.5 * size.0 as FracFloat, 0.5 * size.1 as FracFloat);
+            // Counterclockwise angles around the center, with y pointing up
+            let before = (cy - y + dy).atan2(x - dx - cx);
+            let after = (cy - y).atan2(x - cx);
+            view.rotate(after - before)
+        },
+        _ => ()
+    }
+}
+
+
+/// Feeds drags to the camera, which keeps panning for a moment after the button or finger is released.
+/// Pinches have no momentum.
+fn track_drag(camera: &mut Camera, pixel_scale: &PixelScale, size: (u32, u32), touch: &Touch, event: &Event) {
+    match *event {
+        Event::MouseMotion {timestamp, mousestate, xrel, yrel, ..} if mousestate.left() => {
+            let (dx, dy) = pixel_scale.to_pixels(xrel as FracFloat, yrel as FracFloat);
+            camera.drag(dx, dy, timestamp)
+        },
+        Event::FingerMotion {timestamp, dx, dy, ..} if touch.fingers() == 1 => {
+            camera.drag(dx as FracFloat * size.0 as FracFloat, dy as FracFloat * size.1 as FracFloat, timestamp)
+        },
+        Event::FingerMotion {..} => camera.hold(),
+        Event::MouseButtonUp {timestamp, mouse_btn: MouseButton::Left, ..} => camera.release(timestamp),
+        Event::FingerUp {timestamp, ..} => camera.release(timestamp),
+        _ => ()
+    }
+}
 
-    let gl = video_subsystem.window("latcarf", 1920, 1080)
-        .position_centered()
-        .build_glium()
-        .unwrap();
-    // video_subsystem.gl_set_swap_interval(SwapInterval::Immediate).unwrap();
-    let mut event_pump = sdl_context.event_pump().unwrap();
-    println!("Initialized GPU context.");
 
-    let font = load_default_fonts(); 
-    let font_atlas: font_kit::canvas::Canvas = generate_atlas(&font, 32.0, None);
-    let font_atlas_img = ImageBuffer::<Rgb<u8>, Vec<u8>>::from_raw(
-        font_atlas.size.x() as u32, font_atlas.size.y() as u32, font_atlas.pixels
-    ).unwrap();
-    font_atlas_img.save("font_atlas.png").unwrap();
+/// Action of a key or controller button press. Held keys only repeat actions that change the location,
+/// number keys without an action recall or store bookmarks.
+fn pressed_action(controls: &Controls, bookmarks: &mut Bookmarks, location: &mut Location, event: &Event) -> Option<Action> {
+    match *event {
+        Event::KeyDown {keycode: Some(keycode), keymod, repeat, ..} => match controls.action(keycode) {
+            Some(action) => Some(action).filter(|action| !repeat || action.repeats()),
+            None => {
+                bookmarks.handle_key(keycode, keymod, location);
+                None
+            },
+        },
+        Event::ControllerButtonDown {button, ..} => gamepad::button_action(button),
+        _ => None,
+    }
+}
 
 
+/// Game controller input, and touch input without SDL's emulated mouse events.
+/// Without game controller support the session continues with mouse, keyboard and touch input.
+fn open_gamepads(sdl_context: &sdl2::Sdl) -> Gamepads {
+    // Touches are handled by `Touch`, emulated mouse drags would pan a second time
+    sdl2::hint::set("SDL_TOUCH_MOUSE_EVENTS", "0");
+    let subsystem = sdl_context.game_controller()
+        .inspect_err(|err| log::warn!("Game controllers unavailable: {}", err))
+        .ok();
+    Gamepads::new(subsystem)
+}
+
+
+/// Prints where a screenshot was saved. A failed screenshot is reported without ending the session.
+fn report_screenshot(result: Result<PathBuf, ExportError>) {
+    match result {
+        Ok(path) => println!("Saved screenshot {}.", path.display()),
+        Err(err) => eprintln!("Screenshot failed: {}", err),
+    }
+}
 
+
+/// Two triangles covering the whole viewport, the fractal is computed in the fragment shader.
+fn fullscreen_quad<F: Facade>(gl: &F) -> Result<VertexBuffer<Vertex>, AppError> {
     let demo_rectangle = vec![
         Vertex{ position: [-1.0, -1.0] },
         Vertex{ position: [ 1.0, -1.0] },
@@ -116,119 +332,446 @@ fn gpu_mode() {
         Vertex{ position: [ 1.0,  1.0] },
         Vertex{ position: [-1.0,  1.0] },
     ];
+    Ok(VertexBuffer::new(gl, &demo_rectangle)?)
+}
+
 
+/// Program computing the raw samples, to be drawn into a `sample_texture`.
+fn mandelbrot_program<F: Facade>(gl: &F) -> Result<glium::Program, AppError> {
     let vert_shader = String::from_utf8_lossy(include_bytes!("../res/mandelbrot.vert"));
     let frag_shader_preamble = "#version 330";
-    let frag_shader_colormap = String::from_utf8_lossy(include_bytes!("../res/IDL_Waves.frag"));
     let frag_shader_main = String::from_utf8_lossy(include_bytes!("../res/mandelbrot.frag"));
+    let frag_shader = [frag_shader_preamble, &frag_shader_main].join("\n");
+
+    glium::Program::from_source(gl, &vert_shader, &frag_shader, None)
+        .map_err(|err| AppError::Shader(format_glsl_error(err, &frag_shader, &vert_shader)))
+}
+
+
+/// Program coloring a sample texture with the given colormap.
+fn coloring_program<F: Facade>(gl: &F, colormap: Colormap) -> Result<glium::Program, AppError> {
+    let vert_shader = String::from_utf8_lossy(include_bytes!("../res/mandelbrot.vert"));
+    let frag_shader_preamble = "#version 330";
+    let frag_shader_colormap = String::from_utf8_lossy(colormap.shader_source());
+    let frag_shader_main = String::from_utf8_lossy(include_bytes!("../res/coloring.frag"));
     let frag_shader = [frag_shader_preamble, &frag_shader_colormap, &frag_shader_main].join("\n");
 
-    let indices = glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList);
-    let shader = match glium::Program::from_source(&gl, &vert_shader, &frag_shader, None) {
-        Ok(s) => s,
-        Err(shader_err) => { print_glsl_error(shader_err, &frag_shader, &vert_shader); process::exit(1) }
-    };
-    let vbo = VertexBuffer::new(&gl, &demo_rectangle).unwrap();
-    let (w, h) = gl.get_framebuffer_dimensions();
-    let mut scale = 2.0 / u32::min(w, h) as f32;
-    let mut offset = (0.0, 0.0);
+    glium::Program::from_source(gl, &vert_shader, &frag_shader, None)
+        .map_err(|err| AppError::Shader(format_glsl_error(err, &frag_shader, &vert_shader)))
+}
+
+
+/// Program drawing the orbit overlay in a single color, from points in the complex plane.
+fn orbit_program<F: Facade>(gl: &F) -> Result<glium::Program, AppError> {
+    let vert_shader = String::from_utf8_lossy(include_bytes!("../res/orbit.vert"));
+    let frag_shader_preamble = "#version 330";
+    let frag_shader_main = String::from_utf8_lossy(include_bytes!("../res/orbit.frag"));
+    let frag_shader = [frag_shader_preamble, &frag_shader_main].join("\n");
+
+    glium::Program::from_source(gl, &vert_shader, &frag_shader, None)
+        .map_err(|err| AppError::Shader(format_glsl_error(err, &frag_shader, &vert_shader)))
+}
+
+
+/// Orbit points in the complex plane, none for an empty orbit. Built once per orbit, `res/orbit.vert` applies the view.
+fn orbit_vertices<F: Facade>(gl: &F, orbit: &[(FracFloat, FracFloat)]) -> Result<Option<VertexBuffer<Vertex>>, AppError> {
+    if orbit.is_empty() {
+        return Ok(None);
+    }
+    let vertices: Vec<Vertex> = orbit.iter()
+        .map(|&(re, im)| Vertex{ position: [re as f32, im as f32] })
+        .collect();
+    Ok(Some(VertexBuffer::new(gl, &vertices)?))
+}
+
+
+/// Draws the orbit overlay with the SDL2_gfx primitives.
+fn draw_orbit(canvas: &Canvas<Window>, orbit: &OrbitOverlay, view: &View, size: (u32, u32)) -> Result<(), AppError> {
+    let color = Color::RGBA(255, 255, 255, 200);
+    let segments = orbit.pixel_segments(view, size);
+    for &(from, to) in &segments {
+        canvas.aa_line(from.0, from.1, to.0, to.1, color).map_err(AppError::Sdl)?;
+    }
+    let points: BTreeSet<(i16, i16)> = segments.into_iter().flat_map(|(from, to)| [from, to]).collect();
+    for (x, y) in points {
+        canvas.filled_circle(x, y, 2, color).map_err(AppError::Sdl)?;
+    }
+    Ok(())
+}
+
+
+/// Float texture holding a sample per pixel, see `res/mandelbrot.frag`.
+fn sample_texture<F: Facade>(gl: &F, size: (u32, u32)) -> Result<Texture2d, AppError> {
+    Texture2d::empty_with_format(gl, UncompressedFloatFormat::F32F32F32F32, MipmapsOption::NoMipmap, size.0, size.1)
+        .map_err(|err| AppError::RenderTarget(err.to_string()))
+}
+
+
+fn gpu_mode(cli: &Args) -> Result<(), AppError> {
+    let sdl_context = sdl2::init().map_err(AppError::Sdl)?;
+    let video_subsystem = sdl_context.video().map_err(AppError::Sdl)?;
 
-    let mut frametimes: VecDeque<u64> = VecDeque::new();
+    let mut gamepads = open_gamepads(&sdl_context);
+    let mut touch = Touch::default();
+
+    let mut gl = window_builder(&video_subsystem, cli).build_glium()?;
+    let mut pacer = FramePacer::new(cli.pacing);
+    pacer.apply_swap_interval(&video_subsystem).map_err(AppError::Sdl)?;
+    let mut event_pump = sdl_context.event_pump().map_err(AppError::Sdl)?;
+    println!("Initialized GPU context.");
+
+    let fonts = load_fonts(cli.font.as_deref())?;
+    let font_atlas = FontAtlas::new(fonts, AtlasKind::SignedDistance { spread: 4.0 }, 32.0, None, &cli.charset)?;
+    if cli.debug {
+        if let Err(err) = font_atlas.save("font_atlas.png") {
+            log::warn!("Failed to save the font atlas: {}", err);
+        }
+    }
+    let mut text_renderer = TextRenderer::new(&gl, font_atlas).map_err(|err| AppError::Shader(err.to_string()))?;
+    let hud_style = TextStyle::default();
+
+    let indices = glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList);
+    let shader = mandelbrot_program(&gl)?;
+    let coloring_programs = Colormap::ALL.iter()
+        .map(|&colormap| coloring_program(&gl, colormap))
+        .collect::<Result<Vec<_>, _>>()?;
+    let vbo = fullscreen_quad(&gl)?;
+    let orbit_shader = orbit_program(&gl)?;
+    let mut orbit = OrbitOverlay::default();
+    let mut orbit_vbo = None;
+    let mut size = gl.get_framebuffer_dimensions();
+    let mut pixel_scale = PixelScale::new(gl.get_window_size(), size);
+    let home = cli.initial_location(size)?;
+    let mut location = home;
+    location.resize(size);
+    let controls = Controls::new(&cli.bind);
+    let mut bookmarks = cli.bookmarks()?;
+    let mut show_help = false;
+    // `location.view` is the target of the camera, which shows `view`
+    let mut camera = Camera::new(location.view);
+    let mut view = camera.view();
+    let mut frame_t = Instant::now();
+    let mut samples = sample_texture(&gl, size)?;
+
+    let mut gpu_timer = GpuTimer::new();
+    let mut gpu_stats = FrameStats::new(STATS_WINDOW);
+    let mut cpu_stats = FrameStats::new(STATS_WINDOW);
+    let mut stats_log = cli.stats.as_ref().map(StatsLog::create).transpose()?;
+    let mut frame: u64 = 0;
+    let mut redraw = true;
+    let mut take_screenshot = false;
+    // The samples are only recomputed when the view or fractal changes, otherwise just the coloring pass runs
+    let mut recompute = true;
     loop {
-        let (w, h) = gl.get_framebuffer_dimensions();
-        for event in event_pump.poll_iter() {
+        let prev = location;
+        // Sleep until something happens while the last frame is up to date
+        let idle = !redraw && !camera.is_moving() && !gamepads.sticks.is_active();
+        let idle_event = if idle { Some(event_pump.wait_event()) } else { None };
+        for event in idle_event.into_iter().chain(event_pump.poll_iter()) {
+            gamepads.handle_event(&event);
+            redraw |= orbit.handle_event(&event, &pixel_scale, &view, size);
             match event {
-                Event::Quit {..} => return,
-                Event::MouseWheel {precise_y, ..} => scale *= (-0.1 * precise_y).exp(),
-                Event::MouseMotion {mousestate, xrel, yrel, ..} if mousestate.left() => {offset.0 -= (xrel as f32)*scale; offset.1 += (yrel as f32)*scale},
-                _ => ()
+                Event::Quit {..} => {
+                    if let Some(log) = stats_log.take() {
+                        log.finish()?;
+                    }
+                    return Ok(())
+                },
+                Event::Window {..} => redraw = true,
+                _ => {
+                    track_drag(&mut camera, &pixel_scale, size, &touch, &event);
+                    touch.handle_event(&event, &mut location.view, size);
+                    navigate(&mut location.view, &pixel_scale, size, &event)
+                },
+            }
+            match pressed_action(&controls, &mut bookmarks, &mut location, &event) {
+                Some(Action::Fullscreen) => toggle_fullscreen(gl.window_mut())?,
+                Some(Action::Screenshot) => {
+                    take_screenshot = true;
+                    redraw = true;
+                },
+                Some(Action::Help) => {
+                    show_help = !show_help;
+                    redraw = true;
+                },
+                Some(Action::Orbit) => {
+                    orbit.enabled = !orbit.enabled;
+                    redraw = true;
+                },
+                Some(action) => {
+                    action.apply(&mut location, &home);
+                },
+                None => (),
+            }
+        }
+        let new_size = gl.get_framebuffer_dimensions();
+        if new_size != size {
+            location.resize(new_size);
+            camera.resize(size, new_size);
+            size = new_size;
+            samples = sample_texture(&gl, size)?;
+            recompute = true;
+        }
+        pixel_scale = PixelScale::new(gl.get_window_size(), size);
+        let prev_view = view;
+        let now = Instant::now();
+        gamepads.sticks.apply(&mut location.view, size, now - frame_t);
+        view = camera.advance(&mut location.view, size, now - frame_t);
+        frame_t = now;
+        if view != prev_view || location.fractal != prev.fractal {
+            recompute = true;
+        }
+        redraw |= recompute || location.coloring != prev.coloring;
+        if !redraw {
+            continue;
+        }
+        let (w, h) = size;
+
+        for (measured_frame, gpu_nanos) in gpu_timer.poll() {
+            gpu_stats.push(gpu_nanos);
+            if let Some(log) = &mut stats_log {
+                log.record_gpu(measured_frame, gpu_nanos)?;
             }
         }
     
         let render_start_t = Instant::now();
         // RENDER START
+        let mut gpu_pending = false;
+        if recompute {
+            let mut sample_tgt = SimpleFrameBuffer::new(&gl, &samples)
+                .map_err(|err| AppError::RenderTarget(err.to_string()))?;
+            let gpu_query = gpu_timer.start(&gl, frame);
+            gpu_pending = gpu_query.is_some();
+            sample_tgt.draw(
+                &vbo,
+                &indices,
+                &shader,
+                &uniform!{
+                    offset: (view.center.0 as f32, view.center.1 as f32),
+                    scale: view.scale as f32,
+                    rotation: (view.angle.cos() as f32, view.angle.sin() as f32),
+                    window_size: (w as f32, h as f32),
+                    formula: location.fractal.formula as i32,
+                    max_depth: location.fractal.max_iterations,
+                },
+                &DrawParameters {
+                    time_elapsed_query: gpu_query,
+                    ..Default::default()
+                }
+            )?;
+            recompute = false;
+        }
+        if take_screenshot {
+            let texels = samples.main_level().first_layer().into_image(None)
+                .expect("sample texture has an image")
+                .raw_read(&glium::Rect{left: 0, bottom: 0, width: w, height: h});
+            report_screenshot(screenshot::save(&Location{view, ..location}, &screenshot::samples_from_texels(texels)));
+            take_screenshot = false;
+        }
         let mut render_tgt = gl.draw();
         render_tgt.clear_color(0.0, 0.0, 0.0, 1.0);
         render_tgt.draw(
             &vbo,
             &indices,
-            &shader,
-            &uniform!{offset: offset, scale: scale, window_size: (w as f32, h as f32)},
+            &coloring_programs[location.coloring.colormap as usize],
+            &uniform!{
+                samples: &samples,
+                mode: location.coloring.mode as i32,
+                palette_offset: location.coloring.offset,
+            },
             &Default::default()
-        ).unwrap();
-        render_tgt.finish().unwrap();
+        )?;
+        if orbit.update(&view, size, &location.fractal) {
+            orbit_vbo = orbit_vertices(&gl, orbit.orbit())?;
+        }
+        if let Some(orbit_vbo) = &orbit_vbo {
+            let orbit_params = DrawParameters {
+                blend: glium::Blend::alpha_blending(),
+                point_size: Some(4.0 * pixel_scale.y as f32),
+                ..Default::default()
+            };
+            let orbit_uniforms = uniform!{
+                offset: (view.center.0 as f32, view.center.1 as f32),
+                scale: view.scale as f32,
+                rotation: (view.angle.cos() as f32, view.angle.sin() as f32),
+                window_size: (w as f32, h as f32),
+                line_color: (1.0f32, 1.0f32, 1.0f32, 0.8f32),
+            };
+            for primitive in [glium::index::PrimitiveType::LineStrip, glium::index::PrimitiveType::Points] {
+                render_tgt.draw(orbit_vbo, glium::index::NoIndices(primitive), &orbit_shader, &orbit_uniforms, &orbit_params)?;
+            }
+        }
+        let mut hud_text = format!(
+            "Re {:+.6}\nIm {:+.6}\nzoom ×{:.3e}\n{} ×{}",
+            view.center.0, view.center.1, view.zoom_level(size),
+            location.fractal.formula, location.fractal.max_iterations
+        );
+        if view.angle != 0.0 {
+            hud_text += &format!("\nrotated {:.1}°", view.angle.to_degrees());
+        }
+        if let Some(summary) = gpu_stats.summary() {
+            hud_text += &format!("\nGPU {}", summary);
+        }
+        if let Some(summary) = cpu_stats.summary() {
+            hud_text += &format!("\nCPU {}", summary);
+        }
+        if show_help {
+            hud_text += &format!("\n\n{}", controls.help_text());
+        } else {
+            hud_text += &format!("\n{}: help", controls.keys(Action::Help));
+        }
+        let hud_pos = pixel_scale.to_pixels(10.0, 10.0);
+        text_renderer.draw(&gl, &mut render_tgt, &hud_text, (hud_pos.0 as f32, hud_pos.1 as f32), &hud_style.scaled(pixel_scale.y as f32));
+        render_tgt.finish()?;
         // RENDER END
-        frametimes.push_back(render_start_t.elapsed().as_nanos() as u64);
-        if frametimes.len() > 10 {
-            let avg_frametime: u64 = (frametimes.iter().sum::<u64>() / frametimes.len() as u64) / 1000;
-            println!("Time per frame: {avg_frametime}us");
-            frametimes.pop_front();
+        let cpu_nanos = render_start_t.elapsed().as_nanos() as u64;
+        cpu_stats.push(cpu_nanos);
+        if let Some(log) = &mut stats_log {
+            log.record_frame(frame, cpu_nanos, gpu_pending)?;
         }
-        std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
+        frame += 1;
+        redraw = false;
+        pacer.end_frame();
     }
 }
 
 
-fn cpu_mode() {
-    let sdl_context = sdl2::init().unwrap();
-    let video_subsystem = sdl_context.video().unwrap();
+fn cpu_mode(cli: &Args) -> Result<(), AppError> {
+    let sdl_context = sdl2::init().map_err(AppError::Sdl)?;
+    let video_subsystem = sdl_context.video().map_err(AppError::Sdl)?;
     
-    let window = video_subsystem.window("latcarf", 1920, 1080)
-        .position_centered()
-        .build()
-        .unwrap();
-    let mut event_pump = sdl_context.event_pump().unwrap();
-    let mut canvas = window.into_canvas().build().unwrap();
+    let mut gamepads = open_gamepads(&sdl_context);
+    let mut touch = Touch::default();
+    let mut orbit = OrbitOverlay::default();
+
+    let window = window_builder(&video_subsystem, cli).build()?;
+    let mut event_pump = sdl_context.event_pump().map_err(AppError::Sdl)?;
+    let mut canvas_builder = window.into_canvas();
+    if cli.pacing.uses_vsync() {
+        canvas_builder = canvas_builder.present_vsync();
+    }
+    let mut canvas = canvas_builder.build()?;
+    let texture_creator = canvas.texture_creator();
+    let mut pacer = FramePacer::new(cli.pacing);
     println!("Initialized window manager.");
     
     canvas.set_draw_color(Color::RGB(0, 0, 0));
     canvas.clear();
     canvas.present();
+    let mut size = canvas.output_size().map_err(AppError::Sdl)?;
+    let mut pixel_scale = PixelScale::new(canvas.window().size(), size);
+    let home = cli.initial_location(size)?;
+    let mut location = home;
+    location.resize(size);
+    let mut render = cli.strategy.start(location.view, size, location.fractal);
+    let controls = Controls::new(&cli.bind);
+    let mut bookmarks = cli.bookmarks()?;
+    println!("Press {} for help.", controls.keys(Action::Help));
+    let mut pixels = vec![0; 3 * size.0 as usize * size.1 as usize];
+    let mut texture = texture_creator.create_texture_streaming(PixelFormatEnum::RGB24, size.0, size.1)
+        .map_err(|err| AppError::RenderTarget(err.to_string()))?;
     
-    let mut frametimes: VecDeque<u64> = VecDeque::new();
+    let mut frame_stats = FrameStats::new(STATS_WINDOW);
+    let mut stats_log = cli.stats.as_ref().map(StatsLog::create).transpose()?;
+    let mut frame: u64 = 0;
+    let mut redraw = true;
+    let mut take_screenshot = false;
+    let mut frame_t = Instant::now();
     loop {
-        canvas.set_draw_color(Color::RGB(0, 0, 0));
-        canvas.clear();
-        for event in event_pump.poll_iter() {
+        let prev = location;
+        // Sleep until something happens while the last frame is up to date
+        let idle = !redraw && render.is_done() && !gamepads.sticks.is_active();
+        let idle_event = if idle { Some(event_pump.wait_event()) } else { None };
+        for event in idle_event.into_iter().chain(event_pump.poll_iter()) {
+            gamepads.handle_event(&event);
+            redraw |= orbit.handle_event(&event, &pixel_scale, &location.view, size);
             match event {
-                Event::Quit {..} => return,
-                _ => ()
+                Event::Quit {..} => {
+                    if let Some(log) = stats_log.take() {
+                        log.finish()?;
+                    }
+                    return Ok(())
+                },
+                Event::Window {..} => redraw = true,
+                _ => {
+                    touch.handle_event(&event, &mut location.view, size);
+                    navigate(&mut location.view, &pixel_scale, size, &event)
+                },
             }
+            match pressed_action(&controls, &mut bookmarks, &mut location, &event) {
+                Some(Action::Fullscreen) => toggle_fullscreen(canvas.window_mut())?,
+                Some(Action::Screenshot) => take_screenshot = true,
+                // There is no HUD in this mode
+                Some(Action::Help) => println!("{}", controls.help_text()),
+                Some(Action::Orbit) => {
+                    orbit.enabled = !orbit.enabled;
+                    redraw = true;
+                },
+                Some(action) => {
+                    action.apply(&mut location, &home);
+                },
+                None => (),
+            }
+        }
+        let now = Instant::now();
+        gamepads.sticks.apply(&mut location.view, size, now - frame_t);
+        frame_t = now;
+        redraw |= location.coloring != prev.coloring;
+        let new_size = canvas.output_size().map_err(AppError::Sdl)?;
+        if new_size != size {
+            location.resize(new_size);
+            size = new_size;
+            texture = texture_creator.create_texture_streaming(PixelFormatEnum::RGB24, size.0, size.1)
+                .map_err(|err| AppError::RenderTarget(err.to_string()))?;
+            pixels = vec![0; 3 * size.0 as usize * size.1 as usize];
+        }
+        pixel_scale = PixelScale::new(canvas.window().size(), size);
+        // Pans by whole pixels keep the rendered pixels, otherwise unfinished work of the previous view is dropped
+        let view = location.view;
+        if size != render.size() || location.fractal != *render.fractal() || (view != *render.view() && !render.pan(view)) {
+            render = cli.strategy.start(view, size, location.fractal);
+        }
+        if take_screenshot {
+            // The unfinished refinement passes are not worth saving
+            render.refine(Duration::MAX);
+            report_screenshot(screenshot::save(&location, render.samples()));
+            take_screenshot = false;
+            redraw = true;
+        }
+        if !redraw && render.is_done() {
+            continue;
         }
     
         let render_start_t = Instant::now();
-        render_cpu(&mut canvas, (0.0, 0.0)).unwrap();
-        frametimes.push_back(render_start_t.elapsed().as_nanos() as u64);
-        if frametimes.len() > 10 {
-            let avg_frametime: u64 = (frametimes.iter().sum::<u64>() / frametimes.len() as u64) / 1000;
-            println!("Time per frame: {avg_frametime}us");
-            frametimes.pop_front();
+        render.refine(REFINE_BUDGET);
+        let cpu_nanos = render_start_t.elapsed().as_nanos() as u64;
+        location.coloring.apply(render.samples(), &mut pixels);
+        texture.update(None, &pixels, 3 * size.0 as usize)
+            .map_err(|err| AppError::RenderTarget(err.to_string()))?;
+        canvas.copy(&texture, None, None).map_err(AppError::Sdl)?;
+        orbit.update(&view, size, &location.fractal);
+        draw_orbit(&canvas, &orbit, &view, size)?;
+        frame_stats.push(cpu_nanos);
+        if let Some(log) = &mut stats_log {
+            log.record_frame(frame, cpu_nanos, false)?;
         }
-        canvas.present();
-        std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
-    }
-}
-
-
-fn render_cpu<T: RenderTarget>(canvas: &mut Canvas<T>, offset: (FracFloat, FracFloat)) -> Result<(), String> {
-    let (w, h) = canvas.output_size()?;
-    let (real_offset, imag_offset) = offset;
-    let scale: FracFloat = 4.0 / (w as FracFloat);
-    for (x, y) in (0..w).cartesian_product(0..h) {
-        let c_real = (x as FracFloat - 0.5 * w as FracFloat) * scale + real_offset;
-        let c_imag = (y as FracFloat - 0.5 * h as FracFloat) * scale + imag_offset;
-        if let Some((_, dist)) = mandelbrot_depth(c_real, c_imag) {
-            if dist > scale*0.25 {
-                canvas.pixel(x as i16, y as i16, Color::RGB(255, 255, 255))?;
+        if render.is_done() {
+            if let Some(summary) = frame_stats.summary() {
+                println!("Time per frame: {}", summary);
             }
         }
+        frame += 1;
+        canvas.present();
+        redraw = false;
+        pacer.end_frame();
     }
-    Ok(())
 }
 
 
 /// Calculates the depth of the mandelbrot fractal for given C real and imaginary part.
 /// Returns tuple of depth and distance to set if outside of the set.
+/// Reference for the tests of the kernel and the renderers, which use `kernel` instead.
+#[cfg(test)]
 fn mandelbrot_depth(c_real: FracFloat, c_imag: FracFloat) -> Option<(u32, FracFloat)> {
     // z_n+1 = z_n^2 + c
     // Translated from complex into real operations (indices omitted):
@@ -0,0 +1,148 @@
+/// Orbit overlay of the interactive modes, for teaching and for checking formulas.
+/// Shows the iterates z0, z1, ... of the point under the mouse as connected points over the fractal.
+/// A middle click pins the point, so that it stays while navigating, another one releases it.
+/// The orbit is kept in the complex plane and only iterated again when the point or the fractal changes.
+
+use std::collections::BTreeSet;
+
+use sdl2::event::{Event, WindowEvent};
+use sdl2::mouse::MouseButton;
+
+use crate::fractal::Fractal;
+use crate::kernel;
+use crate::view::{PixelScale, View};
+use crate::FracFloat;
+
+
+type Point = (FracFloat, FracFloat);
+
+/// Most points drawn, orbits of points inside the set run up to the iteration cap.
+const ORBIT_LIMIT: usize = 10_000;
+
+
+#[derive(Default)]
+pub struct OrbitOverlay {
+    pub enabled: bool,
+    /// Mouse position in pixels.
+    hover: Option<Point>,
+    /// Pinned point in the complex plane.
+    pinned: Option<Point>,
+    /// Point and fractal of the current orbit, along with the orbit in the complex plane.
+    orbit: Option<(Point, Fractal, Vec<Point>)>,
+}
+
+impl OrbitOverlay {
+    /// Follows the mouse over the `view` shown in a window of `size` pixels.
+    /// Returns true if the shown orbit changed.
+    pub fn handle_event(&mut self, event: &Event, pixel_scale: &PixelScale, view: &View, size: (u32, u32)) -> bool {
+        let following = self.enabled && self.pinned.is_none();
+        match *event {
+            Event::MouseMotion {x, y, ..} => {
+                self.hover = Some(pixel_scale.to_pixels(x as FracFloat, y as FracFloat));
+                following
+            },
+            Event::Window {win_event: WindowEvent::Leave, ..} => {
+                self.hover = None;
+                following
+            },
+            Event::MouseButtonDown {mouse_btn: MouseButton::Middle, x, y, ..} if self.enabled => {
+                self.pinned = match self.pinned {
+                    Some(_) => None,
+                    None => {
+                        let (x, y) = pixel_scale.to_pixels(x as FracFloat, y as FracFloat);
+                        Some(view.pixel_to_complex(x, y, size))
+                    },
+                };
+                true
+            },
+            _ => false,
+        }
+    }
+
+    /// Iterates the pinned point or the point under the mouse if it or the fractal changed.
+    /// Returns true if the orbit changed.
+    pub fn update(&mut self, view: &View, size: (u32, u32), fractal: &Fractal) -> bool {
+        let c = self.pinned.or_else(|| self.hover.map(|(x, y)| view.pixel_to_complex(x, y, size)))
+            .filter(|_| self.enabled);
+        match (c, &self.orbit) {
+            (Some(c), Some((last_c, last_fractal, _))) if c == *last_c && fractal == last_fractal => false,
+            (None, None) => false,
+            (Some(c), _) => {
+                self.orbit = Some((c, *fractal, kernel::orbit(fractal, c, ORBIT_LIMIT)));
+                true
+            },
+            (None, Some(_)) => {
+                self.orbit = None;
+                true
+            },
+        }
+    }
+
+    /// The orbit in the complex plane as of the last `update`, empty while disabled.
+    pub fn orbit(&self) -> &[Point] {
+        self.orbit.as_ref().map_or(&[], |(_, _, orbit)| orbit)
+    }
+
+    /// Segments between successive iterates in whole pixels, each drawn once.
+    /// Orbits converging to a cycle repeat the same few segments many times.
+    pub fn pixel_segments(&self, view: &View, size: (u32, u32)) -> BTreeSet<((i16, i16), (i16, i16))> {
+        // Float to integer casts saturate, far away iterates end up on the border of the range
+        let pixel = |&(re, im): &Point| {
+            let (x, y) = view.complex_to_pixel(re, im, size);
+            (x as i16, y as i16)
+        };
+        let orbit = self.orbit();
+        orbit.iter().map(pixel)
+            .zip(orbit.iter().skip(1).map(pixel))
+            .collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_orbit_follows_pointer() {
+        let size = (800, 600);
+        let mut view = View::at((-0.5, 0.0), 1.5, size);
+        view.rotate(0.3);
+        let fractal = Fractal::default();
+        let mut overlay = OrbitOverlay{hover: Some((500.5, 200.5)), ..Default::default()};
+        assert!(!overlay.update(&view, size, &fractal));
+        assert!(overlay.orbit().is_empty());
+
+        overlay.enabled = true;
+        assert!(overlay.update(&view, size, &fractal));
+        // z1 = c is the point under the pointer, in the middle of a pixel
+        let c = view.pixel_to_complex(500.5, 200.5, size);
+        assert_eq!(overlay.orbit()[..2], [(0.0, 0.0), c]);
+        assert!(!overlay.update(&view, size, &fractal));
+        let origin = view.complex_to_pixel(0.0, 0.0, size);
+        let first = ((origin.0 as i16, origin.1 as i16), (500, 200));
+        assert!(overlay.pixel_segments(&view, size).contains(&first));
+
+        // A pinned point stays while the pointer moves on
+        overlay.pinned = Some(c);
+        overlay.hover = None;
+        assert!(!overlay.update(&view, size, &fractal));
+        let deeper = Fractal{max_iterations: 1000, ..fractal};
+        assert!(overlay.update(&view, size, &deeper));
+
+        overlay.enabled = false;
+        assert!(overlay.update(&view, size, &fractal));
+        assert!(overlay.orbit().is_empty());
+    }
+
+    #[test]
+    fn test_cycles_draw_few_segments() {
+        let size = (800, 600);
+        let view = View::fit(size);
+        let mut overlay = OrbitOverlay{enabled: true, pinned: Some((-1.0, 0.0)), ..Default::default()};
+        overlay.update(&view, size, &Fractal::default());
+        // c = -1 alternates between 0 and -1
+        assert_eq!(overlay.orbit().len(), 201);
+        assert_eq!(overlay.pixel_segments(&view, size).len(), 2);
+    }
+}
@@ -0,0 +1,116 @@
+/// Frame pacing for the interactive render loops.
+/// Presentation is either synchronized with the display through the swap interval,
+/// or limited to a fixed rate by sleeping between frames.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use sdl2::video::SwapInterval;
+use sdl2::VideoSubsystem;
+
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum FrameMode {
+    /// Wait for vertical blank before presenting.
+    #[default]
+    Vsync,
+    /// Vsync, but frames that miss the vertical blank are presented immediately (late swap tearing).
+    Adaptive,
+    /// Present as fast as possible.
+    Uncapped,
+    /// Limit the frame rate to the given frames per second, without vsync.
+    Fixed(u32),
+}
+
+impl fmt::Display for FrameMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FrameMode::Vsync => write!(f, "vsync"),
+            FrameMode::Adaptive => write!(f, "adaptive"),
+            FrameMode::Uncapped => write!(f, "uncapped"),
+            FrameMode::Fixed(fps) => write!(f, "{}", fps),
+        }
+    }
+}
+
+impl FromStr for FrameMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<FrameMode, String> {
+        match s {
+            "vsync" => Ok(FrameMode::Vsync),
+            "adaptive" => Ok(FrameMode::Adaptive),
+            "uncapped" => Ok(FrameMode::Uncapped),
+            _ => match s.parse::<u32>() {
+                Ok(fps) if fps > 0 => Ok(FrameMode::Fixed(fps)),
+                _ => Err(format!("Expected 'vsync', 'adaptive', 'uncapped' or a frame rate, got '{}'", s)),
+            },
+        }
+    }
+}
+
+impl FrameMode {
+    /// True if presenting a frame waits for the display.
+    pub fn uses_vsync(&self) -> bool {
+        matches!(self, FrameMode::Vsync | FrameMode::Adaptive)
+    }
+}
+
+
+pub struct FramePacer {
+    mode: FrameMode,
+    last_frame: Instant,
+}
+
+impl FramePacer {
+    pub fn new(mode: FrameMode) -> FramePacer {
+        FramePacer{mode, last_frame: Instant::now()}
+    }
+
+    /// Sets the OpenGL swap interval for the current context.
+    /// Adaptive vsync is not supported everywhere, regular vsync is used instead then.
+    pub fn apply_swap_interval(&self, video_subsystem: &VideoSubsystem) -> Result<(), String> {
+        let interval = match self.mode {
+            FrameMode::Vsync => SwapInterval::VSync,
+            FrameMode::Adaptive => SwapInterval::LateSwapTearing,
+            FrameMode::Uncapped | FrameMode::Fixed(_) => SwapInterval::Immediate,
+        };
+        match video_subsystem.gl_set_swap_interval(interval) {
+            Err(err) if self.mode == FrameMode::Adaptive => {
+                log::info!("Adaptive vsync is not available ({}), using vsync", err);
+                video_subsystem.gl_set_swap_interval(SwapInterval::VSync)
+            },
+            result => result,
+        }
+    }
+
+    /// Call after presenting a frame. Sleeps for the rest of the frame period in fixed rate mode,
+    /// taking the time spent rendering into account.
+    pub fn end_frame(&mut self) {
+        if let FrameMode::Fixed(fps) = self.mode {
+            let period = Duration::from_secs_f64(1.0 / fps as f64);
+            let elapsed = self.last_frame.elapsed();
+            if elapsed < period {
+                std::thread::sleep(period - elapsed);
+            }
+        }
+        self.last_frame = Instant::now();
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_mode_parse() {
+        assert_eq!("vsync".parse(), Ok(FrameMode::Vsync));
+        assert_eq!("adaptive".parse(), Ok(FrameMode::Adaptive));
+        assert_eq!("uncapped".parse(), Ok(FrameMode::Uncapped));
+        assert_eq!("144".parse(), Ok(FrameMode::Fixed(144)));
+        assert!("0".parse::<FrameMode>().is_err());
+        assert!("fast".parse::<FrameMode>().is_err());
+    }
+}
@@ -0,0 +1,137 @@
+/// Skyline rectangle packer used for the font atlas.
+/// The skyline is the upper contour of all placed rectangles, stored as horizontal
+/// segments from left to right. New rectangles are placed on top of it where they end up lowest.
+
+
+#[derive(Clone, Copy, Debug)]
+struct SkylineSegment {
+    x: i32,
+    y: i32,
+    width: i32,
+}
+
+
+pub struct SkylinePacker {
+    width: i32,
+    height: i32,
+    skyline: Vec<SkylineSegment>,
+}
+
+impl SkylinePacker {
+    pub fn new(width: i32, height: i32) -> SkylinePacker {
+        SkylinePacker{width, height, skyline: vec![SkylineSegment{x: 0, y: 0, width}]}
+    }
+
+    pub fn size(&self) -> (i32, i32) {
+        (self.width, self.height)
+    }
+
+    /// Finds a place for a `w` x `h` rectangle and returns its top left corner,
+    /// or `None` if it does not fit into the remaining space.
+    pub fn pack(&mut self, w: i32, h: i32) -> Option<(i32, i32)> {
+        if w <= 0 || h <= 0 {
+            return Some((0, 0));
+        }
+        // Lowest resulting top edge wins, ties go to the narrower segment to reduce waste
+        let (index, y) = (0..self.skyline.len())
+            .filter_map(|i| self.fit(i, w, h).map(|y| (i, y)))
+            .min_by_key(|&(i, y)| (y + h, self.skyline[i].width))?;
+        let x = self.skyline[index].x;
+        self.skyline.insert(index, SkylineSegment{x, y: y + h, width: w});
+
+        // Cut away the segments now covered by the new one
+        let right = x + w;
+        let i = index + 1;
+        while i < self.skyline.len() && self.skyline[i].x < right {
+            let seg = &mut self.skyline[i];
+            let overlap = right - seg.x;
+            if overlap >= seg.width {
+                self.skyline.remove(i);
+            } else {
+                seg.x += overlap;
+                seg.width -= overlap;
+                break;
+            }
+        }
+        self.merge();
+        Some((x, y))
+    }
+
+    /// Enlarges the packing area. Existing placements are kept.
+    pub fn grow(&mut self, width: i32, height: i32) {
+        assert!(width >= self.width && height >= self.height, "Packer can only grow");
+        if width > self.width {
+            self.skyline.push(SkylineSegment{x: self.width, y: 0, width: width - self.width});
+            self.merge();
+        }
+        self.width = width;
+        self.height = height;
+    }
+
+    /// Returns the top edge of a `w` x `h` rectangle resting on the skyline at segment `index`.
+    fn fit(&self, index: usize, w: i32, h: i32) -> Option<i32> {
+        let x = self.skyline[index].x;
+        if x + w > self.width {
+            return None;
+        }
+        let mut y = 0;
+        let mut remaining = w;
+        for seg in &self.skyline[index..] {
+            if remaining <= 0 {
+                break;
+            }
+            y = y.max(seg.y);
+            remaining -= seg.width;
+        }
+        if y + h > self.height { None } else { Some(y) }
+    }
+
+    /// Joins neighbouring segments of equal height.
+    fn merge(&mut self) {
+        let mut i = 0;
+        while i + 1 < self.skyline.len() {
+            if self.skyline[i].y == self.skyline[i + 1].y {
+                self.skyline[i].width += self.skyline[i + 1].width;
+                self.skyline.remove(i + 1);
+            } else {
+                i += 1;
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overlaps(a: (i32, i32, i32, i32), b: (i32, i32, i32, i32)) -> bool {
+        a.0 < b.0 + b.2 && b.0 < a.0 + a.2 && a.1 < b.1 + b.3 && b.1 < a.1 + a.3
+    }
+
+    #[test]
+    fn test_pack_without_overlap() {
+        let mut packer = SkylinePacker::new(64, 64);
+        let mut placed = Vec::new();
+        for i in 0..40 {
+            let (w, h) = (3 + i % 7, 4 + (i * 5) % 9);
+            let (x, y) = packer.pack(w, h).expect("Rectangles should fit");
+            assert!(x >= 0 && y >= 0 && x + w <= 64 && y + h <= 64);
+            for &other in &placed {
+                assert!(!overlaps((x, y, w, h), other));
+            }
+            placed.push((x, y, w, h));
+        }
+    }
+
+    #[test]
+    fn test_pack_full_and_grow() {
+        let mut packer = SkylinePacker::new(16, 16);
+        assert_eq!(packer.pack(16, 10), Some((0, 0)));
+        assert_eq!(packer.pack(8, 8), None);
+        packer.grow(32, 16);
+        assert_eq!(packer.pack(8, 8), Some((16, 0)));
+        assert_eq!(packer.pack(20, 4), Some((0, 10)));
+        assert_eq!(packer.size(), (32, 16));
+    }
+}
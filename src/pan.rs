@@ -0,0 +1,137 @@
+/// Reuse of rendered pixels when the view is moved by whole pixels.
+/// The image buffers are shifted and only the exposed strips at the edges need to be computed.
+
+use std::ops::Range;
+
+use crate::view::View;
+
+
+/// Pixel ranges along x and y.
+pub type Region = (Range<u32>, Range<u32>);
+
+
+/// Offset `(dx, dy)` such that pixel (x, y) of `new` shows the point of pixel (x + dx, y + dy) of `old`.
+/// Returns `None` unless the views only differ by a translation of whole pixels.
+pub fn pixel_shift(old: &View, new: &View) -> Option<(i64, i64)> {
+    if old.scale != new.scale || old.angle != new.angle {
+        return None;
+    }
+    let (dx, dy) = old.plane_to_pixels(new.center.0 - old.center.0, new.center.1 - old.center.1);
+    let (dx_round, dy_round) = (dx.round(), dy.round());
+    if (dx - dx_round).abs() > 1e-3 || (dy - dy_round).abs() > 1e-3 {
+        return None;
+    }
+    Some((dx_round as i64, dy_round as i64))
+}
+
+
+/// Moves the contents of an image with `channels` values per pixel by `shift`, see `pixel_shift`.
+/// Pixels that have no source are set to `fill`.
+pub fn shift_buffer<T: Copy>(data: &mut [T], size: (u32, u32), channels: usize, shift: (i64, i64), fill: T) {
+    let (w, h) = (size.0 as i64, size.1 as i64);
+    let old = data.to_vec();
+    // Destination columns with a source pixel
+    let x0 = (-shift.0).clamp(0, w);
+    let x1 = (w - shift.0).clamp(0, w);
+    for y in 0..h {
+        let row = (y * w) as usize * channels;
+        let dst = &mut data[row..row + w as usize * channels];
+        let src_y = y + shift.1;
+        if !(0..h).contains(&src_y) || x0 >= x1 {
+            dst.fill(fill);
+            continue;
+        }
+        let src = (src_y * w + x0 + shift.0) as usize * channels;
+        let (left, right) = (x0 as usize * channels, x1 as usize * channels);
+        dst[..left].fill(fill);
+        dst[left..right].copy_from_slice(&old[src..src + right - left]);
+        dst[right..].fill(fill);
+    }
+}
+
+
+/// Parts of the image that `shift_buffer` filled, as at most two non-overlapping regions.
+pub fn exposed_regions(size: (u32, u32), shift: (i64, i64)) -> Vec<Region> {
+    let (w, h) = (size.0 as i64, size.1 as i64);
+    let columns = if shift.0 >= 0 { (w - shift.0).max(0) as u32..size.0 } else { 0..(-shift.0).min(w) as u32 };
+    let rows = if shift.1 >= 0 { (h - shift.1).max(0) as u32..size.1 } else { 0..(-shift.1).min(h) as u32 };
+    // Full width for the exposed rows, the exposed columns only cover the remaining rows
+    let remaining_rows = if shift.1 >= 0 { 0..rows.start } else { rows.end..size.1 };
+    let mut regions = Vec::new();
+    if !rows.is_empty() {
+        regions.push((0..size.0, rows));
+    }
+    if !columns.is_empty() && !remaining_rows.is_empty() {
+        regions.push((columns, remaining_rows));
+    }
+    regions
+}
+
+
+/// Moves a region along with `shift_buffer`, clipped to the image. `None` if nothing of it remains.
+pub fn shift_region(region: &Region, size: (u32, u32), shift: (i64, i64)) -> Option<Region> {
+    let clip = |range: &Range<u32>, offset: i64, max: u32| {
+        let start = (range.start as i64 - offset).clamp(0, max as i64) as u32;
+        let end = (range.end as i64 - offset).clamp(0, max as i64) as u32;
+        start..end
+    };
+    let columns = clip(&region.0, shift.0, size.0);
+    let rows = clip(&region.1, shift.1, size.1);
+    if columns.is_empty() || rows.is_empty() {
+        None
+    } else {
+        Some((columns, rows))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pixel_shift() {
+        let view = View::fit((100, 80));
+        let mut moved = view;
+        moved.pan(-3.0, 5.0);
+        assert_eq!(pixel_shift(&view, &moved), Some((3, -5)));
+        let x = view.pixel_to_complex(13.0, 20.0, (100, 80));
+        let y = moved.pixel_to_complex(10.0, 25.0, (100, 80));
+        assert!((x.0 - y.0).abs() < 1e-12 && (x.1 - y.1).abs() < 1e-12);
+
+        moved.pan(0.5, 0.0);
+        assert_eq!(pixel_shift(&view, &moved), None);
+        let mut zoomed = view;
+        zoomed.zoom(2.0);
+        assert_eq!(pixel_shift(&view, &zoomed), None);
+    }
+
+    #[test]
+    fn test_shift_buffer_exposes_regions() {
+        let size = (7, 5);
+        for shift in [(2, 1), (-3, 0), (0, -2), (-1, 4), (8, 0), (0, 0)] {
+            let mut data: Vec<i64> = (0..35).collect();
+            shift_buffer(&mut data, size, 1, shift, -1);
+            let exposed = exposed_regions(size, shift);
+            for y in 0..5 {
+                for x in 0..7 {
+                    let value = data[(y * 7 + x) as usize];
+                    let in_regions = exposed.iter().filter(|(xs, ys)| xs.contains(&x) && ys.contains(&y)).count();
+                    if value == -1 {
+                        assert_eq!(in_regions, 1, "shift {:?} pixel {} {}", shift, x, y);
+                    } else {
+                        assert_eq!(in_regions, 0);
+                        let (src_x, src_y) = (x as i64 + shift.0, y as i64 + shift.1);
+                        assert_eq!(value, src_y * 7 + src_x);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_shift_region() {
+        assert_eq!(shift_region(&(0..4, 2..6), (10, 10), (2, -1)), Some((0..2, 3..7)));
+        assert_eq!(shift_region(&(0..4, 2..6), (10, 10), (5, 0)), None);
+    }
+}
@@ -0,0 +1,317 @@
+/// Progressive refinement for the CPU renderer.
+/// A coarse image is computed first and refined in passes of decreasing block size,
+/// so that slow renders show a preview right away. Samples of earlier passes are kept, also when panning
+/// part way through, where the exposed regions join the current pass.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::coloring::Sample;
+use crate::fractal::Fractal;
+use crate::kernel::{escape_lanes, LANES};
+use crate::pan::{exposed_regions, pixel_shift, shift_buffer, shift_region, Region};
+use crate::subdivide::SubdivisionRender;
+use crate::view::View;
+use crate::FracFloat;
+
+
+/// CPU render that is computed in steps, with the intermediate image shown in between.
+pub trait IncrementalRender {
+    fn view(&self) -> &View;
+    fn size(&self) -> (u32, u32);
+    fn fractal(&self) -> &Fractal;
+    /// Samples of all pixels, including preliminary ones of pixels that are not computed yet.
+    fn samples(&self) -> &[Sample];
+    fn is_done(&self) -> bool;
+    /// Continues rendering until `budget` is used up. Some progress is made on every call.
+    fn refine(&mut self, budget: Duration);
+    /// Moves the render to `view`, keeping the computed pixels that stay visible.
+    /// Returns false if that is not possible and the render has to be restarted.
+    fn pan(&mut self, view: View) -> bool;
+}
+
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum CpuStrategy {
+    /// Every pixel is iterated, coarse to fine.
+    #[default]
+    Progressive,
+    /// Mariani-Silver subdivision, skipping the inside of uniform rectangles.
+    Subdivide,
+}
+
+impl fmt::Display for CpuStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CpuStrategy::Progressive => write!(f, "progressive"),
+            CpuStrategy::Subdivide => write!(f, "subdivide"),
+        }
+    }
+}
+
+impl FromStr for CpuStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<CpuStrategy, String> {
+        match s {
+            "progressive" => Ok(CpuStrategy::Progressive),
+            "subdivide" => Ok(CpuStrategy::Subdivide),
+            _ => Err(format!("Expected 'progressive' or 'subdivide', got '{}'", s)),
+        }
+    }
+}
+
+impl CpuStrategy {
+    pub fn start(&self, view: View, size: (u32, u32), fractal: Fractal) -> Box<dyn IncrementalRender> {
+        match self {
+            CpuStrategy::Progressive => Box::new(ProgressiveRender::new(view, size, fractal)),
+            CpuStrategy::Subdivide => Box::new(SubdivisionRender::new(view, size, fractal)),
+        }
+    }
+}
+
+
+/// Block sizes of the refinement passes. The first pass computes every 16th pixel.
+const PASS_STEPS: [u32; 3] = [4, 2, 1];
+
+
+/// First coordinate at or after `start` on a pass grid of `step` through `origin`.
+fn grid_start(start: u32, origin: u32, step: u32) -> u32 {
+    start + (origin % step + step - start % step) % step
+}
+
+
+pub struct ProgressiveRender {
+    view: View,
+    size: (u32, u32),
+    fractal: Fractal,
+    /// Pixels that are not computed yet hold the sample of the enclosing block.
+    samples: Vec<Sample>,
+    pass: usize,
+    /// Regions still to compute in the current pass, first one first. Regions exposed by panning are fresh,
+    /// the earlier passes skipped them, so all pixels of the pass grid are computed in them.
+    regions: Vec<(Region, bool)>,
+    /// Pixel that all pass grids go through, it moves along with the computed pixels when panning.
+    origin: (u32, u32),
+}
+
+impl ProgressiveRender {
+    pub fn new(view: View, size: (u32, u32), fractal: Fractal) -> ProgressiveRender {
+        let pixel_count = size.0 as usize * size.1 as usize;
+        ProgressiveRender{
+            view,
+            size,
+            fractal,
+            samples: vec![Sample::INSIDE; pixel_count],
+            pass: 0,
+            regions: vec![((0..size.0, 0..size.1), false)],
+            origin: (0, 0),
+        }
+    }
+
+    /// Computes the first remaining row of the first region, moving on to the next pass after the last one.
+    fn refine_row(&mut self) {
+        let step = PASS_STEPS[self.pass];
+        if let Some(((columns, rows), fresh)) = self.regions.first().cloned() {
+            let y = grid_start(rows.start, self.origin.1, step);
+            if y < rows.end {
+                // Pixels on the grid of the previous pass are already computed
+                let prev_step = self.pass.checked_sub(1).filter(|_| !fresh).map(|pass| PASS_STEPS[pass]);
+                let origin = self.origin;
+                let on_prev_row = |prev: u32| y == grid_start(y, origin.1, prev);
+                let xs: Vec<u32> = (grid_start(columns.start, origin.0, step)..columns.end).step_by(step as usize)
+                    .filter(|&x| prev_step.is_none_or(|prev| x != grid_start(x, origin.0, prev) || !on_prev_row(prev)))
+                    .collect();
+                self.compute_row(&xs, y, step, &(columns, rows.clone()));
+            }
+            if y + step < rows.end {
+                self.regions[0].0.1.start = y + step;
+            } else {
+                self.regions.remove(0);
+            }
+        }
+
+        if self.regions.is_empty() {
+            self.pass += 1;
+            if self.pass < PASS_STEPS.len() {
+                self.regions.push(((0..self.size.0, 0..self.size.1), false));
+            }
+        }
+    }
+
+    /// Computes the pixels `xs` of row `y`, filling blocks of size `block` within `region`.
+    fn compute_row(&mut self, xs: &[u32], y: u32, block: u32, region: &Region) {
+        for chunk in xs.chunks(LANES) {
+            // Unused lanes get a point that escapes in the first iteration
+            let mut c_real = [4.0; LANES];
+            let mut c_imag = [0.0; LANES];
+            for (lane, &x) in chunk.iter().enumerate() {
+                (c_real[lane], c_imag[lane]) = self.view.pixel_to_complex(x as FracFloat, y as FracFloat, self.size);
+            }
+            for (&x, escape) in chunk.iter().zip(escape_lanes(&self.fractal, &c_real, &c_imag)) {
+                self.set_sample(x, y, block, region, Sample::new(escape, self.view.scale));
+            }
+        }
+    }
+
+    /// Stores a sample in the `block` sized square below and right of its pixel, clipped to `region`.
+    /// Pixels outside of the region may be computed already.
+    fn set_sample(&mut self, x: u32, y: u32, block: u32, region: &Region, sample: Sample) {
+        let w = self.size.0 as usize;
+        let (x, y) = (x as usize, y as usize);
+        let (x_end, y_end) = ((x + block as usize).min(region.0.end as usize), (y + block as usize).min(region.1.end as usize));
+        for by in y..y_end {
+            self.samples[by * w + x..by * w + x_end].fill(sample);
+        }
+    }
+}
+
+impl IncrementalRender for ProgressiveRender {
+    fn view(&self) -> &View {
+        &self.view
+    }
+
+    fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    fn fractal(&self) -> &Fractal {
+        &self.fractal
+    }
+
+    fn samples(&self) -> &[Sample] {
+        &self.samples
+    }
+
+    fn is_done(&self) -> bool {
+        self.pass >= PASS_STEPS.len()
+    }
+
+    /// Computes rows of the current pass, at least one row per call.
+    fn refine(&mut self, budget: Duration) {
+        let start = Instant::now();
+        while !self.is_done() {
+            self.refine_row();
+            if start.elapsed() >= budget {
+                break;
+            }
+        }
+    }
+
+    /// Keeps the samples of all passes so far. Exposed regions join the current pass, or are computed
+    /// at full resolution once all passes are done.
+    fn pan(&mut self, view: View) -> bool {
+        let Some(shift) = pixel_shift(&self.view, &view) else {
+            return false;
+        };
+        shift_buffer(&mut self.samples, self.size, 1, shift, Sample::INSIDE);
+        let size = self.size;
+        let exposed = exposed_regions(size, shift);
+        if !exposed.is_empty() {
+            self.pass = self.pass.min(PASS_STEPS.len() - 1);
+        }
+        self.regions = self.regions.iter()
+            .filter_map(|(region, fresh)| Some((shift_region(region, size, shift)?, *fresh)))
+            .chain(exposed.into_iter().map(|region| (region, true)))
+            .collect();
+        // All pass steps divide the coarsest one
+        let coarsest = PASS_STEPS[0] as i64;
+        self.origin = (
+            (self.origin.0 as i64 - shift.0).rem_euclid(coarsest) as u32,
+            (self.origin.1 as i64 - shift.1).rem_euclid(coarsest) as u32,
+        );
+        self.view = view;
+        true
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mandelbrot_depth;
+
+    /// Samples of a render of `view` from scratch.
+    fn full_render(view: View, size: (u32, u32)) -> Vec<Sample> {
+        let mut full = ProgressiveRender::new(view, size, Fractal::default());
+        full.refine(Duration::MAX);
+        full.samples
+    }
+
+    fn assert_same_samples(render: &[Sample], expected: &[Sample]) {
+        for (sample, expected) in render.iter().zip(expected) {
+            assert_eq!((sample.depth, sample.dist), (expected.depth, expected.dist));
+        }
+    }
+
+    #[test]
+    fn test_pan_matches_full_render() {
+        let size = (37, 22);
+        let mut view = View::fit(size);
+        let mut render = ProgressiveRender::new(view, size, Fractal::default());
+        render.refine(Duration::MAX);
+        for (dx, dy) in [(5.0, -3.0), (-2.0, 0.0), (0.0, 40.0)] {
+            view.pan(dx, dy);
+            assert!(render.pan(view));
+        }
+        render.refine(Duration::MAX);
+        assert!(render.is_done());
+        assert_same_samples(&render.samples, &full_render(view, size));
+        let mut zoomed = view;
+        zoomed.zoom(2.0);
+        assert!(!render.pan(zoomed));
+    }
+
+    #[test]
+    fn test_pan_during_refinement() {
+        let size = (37, 22);
+        let mut view = View::fit(size);
+        let mut render = ProgressiveRender::new(view, size, Fractal::default());
+        // Pans that do not keep the pass grids in place, a few rows into each pass
+        for (pass, rows, (dx, dy)) in [(0, 3, (5.0, -3.0)), (0, 2, (-2.0, 1.0)), (1, 4, (-7.0, -6.0)), (2, 9, (3.0, 2.0))] {
+            while render.pass < pass {
+                render.refine(Duration::ZERO);
+            }
+            for _ in 0..rows {
+                render.refine(Duration::ZERO);
+            }
+            assert_eq!(render.pass, pass);
+            view.pan(dx, dy);
+            assert!(render.pan(view));
+        }
+        assert!(!render.is_done());
+        render.refine(Duration::MAX);
+        assert_same_samples(&render.samples, &full_render(view, size));
+    }
+
+    #[test]
+    fn test_cpu_strategy_parse() {
+        assert_eq!("progressive".parse(), Ok(CpuStrategy::Progressive));
+        assert_eq!("subdivide".parse(), Ok(CpuStrategy::Subdivide));
+        assert!("fast".parse::<CpuStrategy>().is_err());
+    }
+
+    #[test]
+    fn test_refinement_matches_full_render() {
+        let size = (37, 22);
+        let view = View::fit(size);
+        let mut render = ProgressiveRender::new(view, size, Fractal::default());
+        let mut passes = 0;
+        while !render.is_done() {
+            render.refine(Duration::ZERO);
+            passes += 1;
+        }
+        // Zero budget computes one row per call
+        assert_eq!(passes, 6 + 11 + 22);
+        for y in 0..size.1 {
+            for x in 0..size.0 {
+                let (c_real, c_imag) = view.pixel_to_complex(x as FracFloat, y as FracFloat, size);
+                let expected = mandelbrot_depth(c_real, c_imag);
+                let sample = render.samples[(y * size.0 + x) as usize];
+                assert_eq!(sample.depth, expected.map_or(u32::MAX, |(depth, _)| depth));
+                assert_eq!(sample.dist, expected.map_or(0.0, |(_, dist)| (dist / view.scale) as f32));
+            }
+        }
+    }
+}
@@ -0,0 +1,231 @@
+/// Offscreen rendering to files, without opening a window.
+/// The CPU renderer computes the samples, which are then colored into an image and/or exported raw.
+
+use std::f64::consts::TAU;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use clap::Args;
+use image::{DynamicImage, ImageBuffer, Rgb, Rgb32FImage, RgbImage};
+
+use crate::coloring::{Coloring, Sample};
+use crate::error::AppError;
+use crate::export::{write_raw, ExportError, NpyWriter, RawFormat};
+use crate::location::Location;
+use crate::tiles::{render_tile_row, tile_ranges, PngRows};
+use crate::view::{Center, View, WindowSize};
+use crate::FracFloat;
+
+
+#[derive(Args, Debug)]
+#[command(group = clap::ArgGroup::new("outputs").args(["output", "raw"]).required(true).multiple(true))]
+pub struct RenderArgs {
+    /// Image size in pixels [default: size of --location, or 1920x1080]
+    #[arg(long)]
+    size: Option<WindowSize>,
+    /// Complex plane coordinates of the image center, e.g. "-0.745,0.105" [default: center of --location, or 0,0]
+    #[arg(long, allow_hyphen_values = true)]
+    center: Option<Center>,
+    /// Magnification relative to the initial view of the interactive modes [default: zoom of --location, or 1]
+    #[arg(long)]
+    zoom: Option<FracFloat>,
+    /// Counterclockwise rotation of the image in degrees [default: rotation of --location, or 0]
+    #[arg(long, allow_hyphen_values = true)]
+    rotation: Option<FracFloat>,
+    /// Colored image, in a format chosen by the file extension
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Bits per channel of the colored image: "8", "16" (e.g. PNG, TIFF) or "float" (e.g. EXR)
+    #[arg(long, default_value_t)]
+    bit_depth: BitDepth,
+    /// Raw iteration data: ".npy", ".tif"/".tiff", or raw binary with a JSON sidecar for other extensions
+    #[arg(long)]
+    raw: Option<PathBuf>,
+    /// Render in tiles of this many pixels per side and write the files row by row, for images too large
+    /// to hold in memory. Supports PNG output with 8 or 16 bits and NPY raw data.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    tile_size: Option<u32>,
+}
+
+
+/// Channel format of colored images.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum BitDepth {
+    #[default]
+    Eight,
+    Sixteen,
+    /// 32-bit float channels in linear light, as expected by formats like OpenEXR.
+    Float,
+}
+
+impl fmt::Display for BitDepth {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BitDepth::Eight => write!(f, "8"),
+            BitDepth::Sixteen => write!(f, "16"),
+            BitDepth::Float => write!(f, "float"),
+        }
+    }
+}
+
+impl FromStr for BitDepth {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<BitDepth, String> {
+        match s {
+            "8" => Ok(BitDepth::Eight),
+            "16" => Ok(BitDepth::Sixteen),
+            "float" | "32" => Ok(BitDepth::Float),
+            _ => Err(format!("Expected '8', '16' or 'float', got '{}'", s)),
+        }
+    }
+}
+
+
+fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.04045 { v / 12.92 } else { ((v + 0.055) / 1.055).powf(2.4) }
+}
+
+
+/// Colors the samples of an image of `size` pixels with the given channel format.
+pub fn colored_image(coloring: &Coloring, samples: &[Sample], size: (u32, u32), depth: BitDepth) -> DynamicImage {
+    let colors = samples.iter().map(|sample| coloring.color_f32(sample));
+    match depth {
+        BitDepth::Eight => {
+            let pixels = colors.flat_map(|rgb| rgb.map(|v| (v * 255.0).round() as u8)).collect();
+            RgbImage::from_raw(size.0, size.1, pixels).map(DynamicImage::from)
+        },
+        BitDepth::Sixteen => {
+            let pixels = colors.flat_map(|rgb| rgb.map(|v| (v * 65535.0).round() as u16)).collect();
+            ImageBuffer::<Rgb<u16>, _>::from_raw(size.0, size.1, pixels).map(DynamicImage::from)
+        },
+        BitDepth::Float => {
+            let pixels = colors.flat_map(|rgb| rgb.map(srgb_to_linear)).collect();
+            Rgb32FImage::from_raw(size.0, size.1, pixels).map(DynamicImage::from)
+        },
+    }.expect("one color per pixel")
+}
+
+
+fn is_png(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("png"))
+}
+
+
+/// Writes a PNG with 8 or 16 bits per channel and the location in text chunks.
+pub fn write_png(path: &Path, location: &Location, samples: &[Sample], depth: BitDepth) -> Result<(), ExportError> {
+    let mut png = PngRows::create(path, location, depth)?;
+    png.write(samples)?;
+    png.finish()
+}
+
+
+pub fn run(cli: &crate::Args, args: &RenderArgs) -> Result<(), AppError> {
+    let size = args.size.map(|size| (size.width, size.height));
+    let mut location = cli.initial_location(size.unwrap_or((WindowSize::default().width, WindowSize::default().height)))?;
+    // Without an explicit size, that of the loaded location is kept
+    if let Some(size) = size {
+        location.resize(size);
+    }
+    if let Some(center) = args.center {
+        location.view.center = (center.re, center.im);
+    }
+    if let Some(zoom) = args.zoom {
+        location.view = View{angle: location.view.angle, ..View::at(location.view.center, zoom, location.size)};
+    }
+    if let Some(degrees) = args.rotation {
+        location.view.angle = degrees.to_radians().rem_euclid(TAU);
+    }
+    if let Some(tile) = args.tile_size {
+        return run_tiled(cli, args, &location, tile);
+    }
+
+    let size = location.size;
+    let start = Instant::now();
+    let mut render = cli.strategy.start(location.view, size, location.fractal);
+    render.refine(Duration::MAX);
+    println!("Rendered {}x{} in {:.2}s.", size.0, size.1, start.elapsed().as_secs_f64());
+
+    if let Some(path) = &args.output {
+        if is_png(path) && args.bit_depth != BitDepth::Float {
+            write_png(path, &location, render.samples(), args.bit_depth)?;
+        } else {
+            colored_image(&location.coloring, render.samples(), size, args.bit_depth).save(path)?;
+        }
+        println!("Wrote {}.", path.display());
+    }
+    if let Some(path) = &args.raw {
+        write_raw(path, &location, render.samples())?;
+        println!("Wrote {}.", path.display());
+    }
+    Ok(())
+}
+
+
+/// Renders row after row of tiles, streaming each into the output files.
+fn run_tiled(cli: &crate::Args, args: &RenderArgs, location: &Location, tile: u32) -> Result<(), AppError> {
+    let size = location.size;
+    if args.output.as_ref().is_some_and(|path| !is_png(path) || args.bit_depth == BitDepth::Float) {
+        return Err(AppError::Unsupported("tiled rendering writes 8 or 16 bit PNG images".to_string()));
+    }
+    if args.raw.as_ref().is_some_and(|path| RawFormat::from_path(path) != RawFormat::Npy) {
+        return Err(AppError::Unsupported("tiled rendering exports raw data as NPY".to_string()));
+    }
+
+    let mut png = args.output.as_ref()
+        .map(|path| PngRows::create(path, location, args.bit_depth))
+        .transpose()?;
+    let mut npy = args.raw.as_ref().map(|path| NpyWriter::create(path, location)).transpose()?;
+    let start = Instant::now();
+    let row_count = size.1.div_ceil(tile);
+    for (i, rows) in tile_ranges(size.1, tile).enumerate() {
+        let samples = render_tile_row(cli.strategy, location, rows, tile);
+        if let Some(png) = &mut png {
+            png.write(&samples)?;
+        }
+        if let Some(npy) = &mut npy {
+            npy.write(&samples)?;
+        }
+        println!("Rendered tile row {}/{} after {:.1}s.", i + 1, row_count, start.elapsed().as_secs_f64());
+    }
+    if let Some(png) = png {
+        png.finish()?;
+    }
+    if let Some(npy) = npy {
+        npy.finish()?;
+    }
+    for path in args.output.iter().chain(&args.raw) {
+        println!("Wrote {}.", path.display());
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_colored_image_depths() {
+        let samples = [Sample::INSIDE, Sample{depth: 10, z: [3.0, 0.0], dist: 0.05}];
+        let coloring = Coloring::default();
+        let eight = colored_image(&coloring, &samples, (2, 1), BitDepth::Eight).into_rgb8();
+        let sixteen = colored_image(&coloring, &samples, (2, 1), BitDepth::Sixteen).into_rgb16();
+        assert_eq!(eight.get_pixel(0, 0).0, [0, 0, 0]);
+        assert_eq!(eight.get_pixel(1, 0).0, coloring.color(&samples[1]));
+        // 16 bits keep the precision that 8 bits round away
+        let fine = sixteen.get_pixel(1, 0).0;
+        assert!(fine.iter().zip(eight.get_pixel(1, 0).0).any(|(&v16, v8)| v16 != v8 as u16 * 257));
+        assert_eq!(srgb_to_linear(1.0), 1.0);
+    }
+
+    #[test]
+    fn test_bit_depth_parse() {
+        for depth in [BitDepth::Eight, BitDepth::Sixteen, BitDepth::Float] {
+            assert_eq!(depth.to_string().parse(), Ok(depth));
+        }
+        assert!("12".parse::<BitDepth>().is_err());
+    }
+}
@@ -0,0 +1,78 @@
+/// Screenshots of the interactive modes.
+/// The samples of the current view are colored on the CPU and saved as PNG with the location,
+/// so the image contains neither the HUD nor window decorations and can be loaded with `--location`.
+
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::coloring::Sample;
+use crate::export::ExportError;
+use crate::location::Location;
+use crate::render::{write_png, BitDepth};
+
+
+/// Saves a screenshot to a timestamped file in the working directory and returns its path.
+pub fn save(location: &Location, samples: &[Sample]) -> Result<PathBuf, ExportError> {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+    let stem = format!("latcarf-{}", utc_timestamp(secs));
+    let mut path = PathBuf::from(format!("{}.png", stem));
+    let mut n = 1;
+    while path.exists() {
+        n += 1;
+        path = PathBuf::from(format!("{}-{}.png", stem, n));
+    }
+    write_png(&path, location, samples, BitDepth::Eight)?;
+    Ok(path)
+}
+
+
+/// `YYYYMMDD-HHMMSS` in UTC for seconds since the Unix epoch.
+fn utc_timestamp(secs: u64) -> String {
+    let days = (secs / 86400) as i64;
+    let time = secs % 86400;
+    // Civil date from days since 1970-01-01, with years starting in March
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    format!("{:04}{:02}{:02}-{:02}{:02}{:02}", year, month, day, time / 3600, time / 60 % 60, time % 60)
+}
+
+
+/// Samples from the texels of a GPU sample texture, whose rows start at the bottom.
+/// Points inside the set have a negative depth there.
+pub fn samples_from_texels(texels: Vec<Vec<(f32, f32, f32, f32)>>) -> Vec<Sample> {
+    texels.into_iter().rev()
+        .flatten()
+        .map(|(depth, z_real, z_imag, dist)| match depth {
+            depth if depth < 0.0 => Sample::INSIDE,
+            depth => Sample{depth: depth as u32, z: [z_real, z_imag], dist},
+        })
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_utc_timestamp() {
+        assert_eq!(utc_timestamp(0), "19700101-000000");
+        assert_eq!(utc_timestamp(951_827_696), "20000229-123456");
+        assert_eq!(utc_timestamp(1_792_454_399), "20261019-235959");
+    }
+
+    #[test]
+    fn test_samples_from_texels() {
+        let texels = vec![vec![(1.0, 0.0, 0.0, 0.5)], vec![(-1.0, 0.0, 0.0, 0.0)]];
+        let samples = samples_from_texels(texels);
+        assert!(samples[0].is_inside());
+        assert_eq!(samples[1].depth, 1);
+    }
+}
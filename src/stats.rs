@@ -0,0 +1,225 @@
+/// Frame timing statistics.
+/// GPU time is measured with OpenGL timer queries, which report how long the GPU spent
+/// executing draw calls rather than how long it took to submit them.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::Instant;
+
+use glium::backend::Facade;
+use glium::draw_parameters::TimeElapsedQuery;
+
+
+/// Minimum, average and 99th percentile of a set of frame times, in nanoseconds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Summary {
+    pub min: u64,
+    pub avg: u64,
+    pub p99: u64,
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "min {}µs  avg {}µs  p99 {}µs", self.min / 1000, self.avg / 1000, self.p99 / 1000)
+    }
+}
+
+
+/// Rolling window of the most recent frame times.
+pub struct FrameStats {
+    samples: VecDeque<u64>,
+    capacity: usize,
+}
+
+impl FrameStats {
+    pub fn new(capacity: usize) -> FrameStats {
+        FrameStats{samples: VecDeque::with_capacity(capacity), capacity}
+    }
+
+    pub fn push(&mut self, nanos: u64) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(nanos);
+    }
+
+    pub fn summary(&self) -> Option<Summary> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let mut sorted: Vec<u64> = self.samples.iter().copied().collect();
+        sorted.sort_unstable();
+        let p99_idx = ((sorted.len() as f64 * 0.99).ceil() as usize).saturating_sub(1);
+        Some(Summary{
+            min: sorted[0],
+            avg: sorted.iter().sum::<u64>() / sorted.len() as u64,
+            p99: sorted[p99_idx],
+        })
+    }
+}
+
+
+/// Measures GPU execution time of draw calls.
+/// Results arrive a few frames late, queries are only read once the GPU has finished them.
+pub struct GpuTimer {
+    pending: VecDeque<(u64, TimeElapsedQuery)>,
+    supported: bool,
+}
+
+impl GpuTimer {
+    /// Queries that are not read back after this many frames are dropped.
+    const MAX_PENDING: usize = 8;
+
+    pub fn new() -> GpuTimer {
+        GpuTimer{pending: VecDeque::new(), supported: true}
+    }
+
+    /// Creates a query for `frame` to pass in the `DrawParameters` of the draw calls to measure.
+    /// Returns `None` if timer queries are not supported.
+    pub fn start<F: Facade>(&mut self, gl: &F, frame: u64) -> Option<&TimeElapsedQuery> {
+        if !self.supported {
+            return None;
+        }
+        match TimeElapsedQuery::new(gl) {
+            Ok(query) => {
+                if self.pending.len() == GpuTimer::MAX_PENDING {
+                    self.pending.pop_front();
+                }
+                self.pending.push_back((frame, query));
+                self.pending.back().map(|(_, query)| query)
+            },
+            Err(err) => {
+                log::warn!("GPU timing is not available: {}", err);
+                self.supported = false;
+                None
+            }
+        }
+    }
+
+    /// Returns frame numbers and GPU times in nanoseconds of all completed queries, oldest first.
+    pub fn poll(&mut self) -> Vec<(u64, u64)> {
+        let mut results = Vec::new();
+        while self.pending.front().is_some_and(|(_, query)| query.is_ready()) {
+            let (frame, query) = self.pending.pop_front().unwrap();
+            results.push((frame, query.get() as u64));
+        }
+        results
+    }
+}
+
+impl Default for GpuTimer {
+    fn default() -> GpuTimer {
+        GpuTimer::new()
+    }
+}
+
+
+struct LogRow {
+    frame: u64,
+    time: f64,
+    cpu_nanos: u64,
+    /// Waiting for the GPU measurement of the frame.
+    gpu_pending: bool,
+    gpu_nanos: Option<u64>,
+}
+
+/// Writes one CSV row per frame for offline analysis of frame times.
+/// Rows are written in frame order, so a frame waiting for its GPU measurement holds back the later ones.
+pub struct StatsLog {
+    writer: BufWriter<File>,
+    start: Instant,
+    /// Rows not written yet, oldest first.
+    waiting: VecDeque<LogRow>,
+}
+
+impl StatsLog {
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<StatsLog> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "frame,time_s,cpu_us,gpu_us")?;
+        Ok(StatsLog{writer, start: Instant::now(), waiting: VecDeque::new()})
+    }
+
+    /// Logs a rendered frame. If `gpu_pending` is set, the row is completed by `record_gpu` later.
+    pub fn record_frame(&mut self, frame: u64, cpu_nanos: u64, gpu_pending: bool) -> io::Result<()> {
+        let time = self.start.elapsed().as_secs_f64();
+        self.waiting.push_back(LogRow{frame, time, cpu_nanos, gpu_pending, gpu_nanos: None});
+        self.write_completed()
+    }
+
+    /// Completes the row of `frame`. Older rows whose measurement got lost are written without GPU time.
+    pub fn record_gpu(&mut self, frame: u64, gpu_nanos: u64) -> io::Result<()> {
+        for row in self.waiting.iter_mut().take_while(|row| row.frame <= frame) {
+            if row.gpu_pending {
+                row.gpu_pending = false;
+                row.gpu_nanos = (row.frame == frame).then_some(gpu_nanos);
+            }
+        }
+        self.write_completed()
+    }
+
+    /// Writes the rows still waiting for a GPU measurement without GPU time, on exit.
+    pub fn finish(mut self) -> io::Result<()> {
+        while let Some(row) = self.waiting.pop_front() {
+            self.write_row(&row)?;
+        }
+        self.writer.flush()
+    }
+
+    /// Writes the rows up to the first one still waiting for its GPU measurement.
+    fn write_completed(&mut self) -> io::Result<()> {
+        while self.waiting.front().is_some_and(|row| !row.gpu_pending) {
+            let row = self.waiting.pop_front().unwrap();
+            self.write_row(&row)?;
+        }
+        Ok(())
+    }
+
+    fn write_row(&mut self, row: &LogRow) -> io::Result<()> {
+        let gpu = row.gpu_nanos.map(|ns| format!("{:.1}", ns as f64 / 1000.0)).unwrap_or_default();
+        writeln!(self.writer, "{},{:.6},{:.1},{}", row.frame, row.time, row.cpu_nanos as f64 / 1000.0, gpu)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_stats_summary() {
+        let mut stats = FrameStats::new(100);
+        assert!(stats.summary().is_none());
+        for i in 1..=200 {
+            stats.push(i * 1000);
+        }
+        // Only the last 100 samples are kept
+        let summary = stats.summary().unwrap();
+        assert_eq!(summary.min, 101_000);
+        assert_eq!(summary.avg, 150_500);
+        assert_eq!(summary.p99, 199_000);
+    }
+
+    #[test]
+    fn test_stats_log_keeps_pending_rows() {
+        let path = std::env::temp_dir().join(format!("latcarf-stats-{}.csv", std::process::id()));
+        let mut log = StatsLog::create(&path).unwrap();
+        log.record_frame(0, 2000, true).unwrap();
+        log.record_frame(1, 3000, false).unwrap();
+        log.record_frame(2, 4000, true).unwrap();
+        log.record_frame(3, 5000, true).unwrap();
+        log.record_gpu(2, 1500).unwrap();
+        // Frame 0 lost its measurement, frame 3 is still waiting
+        assert_eq!(log.waiting.len(), 1);
+        log.finish().unwrap();
+
+        let csv = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let rows: Vec<(&str, &str)> = csv.lines().skip(1)
+            .map(|line| (line.split(',').next().unwrap(), line.rsplit(',').next().unwrap()))
+            .collect();
+        assert_eq!(rows, [("0", ""), ("1", ""), ("2", "1.5"), ("3", "")]);
+    }
+}
@@ -0,0 +1,252 @@
+/// Mariani-Silver subdivision for the CPU renderer.
+/// Only the borders of rectangles are iterated. The Mandelbrot set and the bands of equal iteration count
+/// are connected, so a rectangle whose border has a single iteration count is filled without iterating its inside.
+
+use std::time::{Duration, Instant};
+
+use crate::fractal::Fractal;
+use crate::kernel::{escape_lanes, LANES};
+use crate::pan::{exposed_regions, pixel_shift, shift_buffer, shift_region, Region};
+use crate::coloring::Sample;
+use crate::progressive::IncrementalRender;
+use crate::view::View;
+use crate::FracFloat;
+
+
+/// Rectangles with a side shorter than this are computed pixel by pixel.
+const MIN_SIZE: u32 = 6;
+
+
+/// Pixel rectangle, all bounds inclusive.
+#[derive(Clone, Copy, Debug)]
+struct Rect {
+    x0: u32,
+    y0: u32,
+    x1: u32,
+    y1: u32,
+}
+
+impl Rect {
+    fn from_region(region: &Region) -> Rect {
+        Rect{x0: region.0.start, y0: region.1.start, x1: region.0.end - 1, y1: region.1.end - 1}
+    }
+
+    fn to_region(self) -> Region {
+        (self.x0..self.x1 + 1, self.y0..self.y1 + 1)
+    }
+
+    fn border(&self) -> Vec<(u32, u32)> {
+        let mut points = Vec::new();
+        for x in self.x0..=self.x1 {
+            points.push((x, self.y0));
+            points.push((x, self.y1));
+        }
+        for y in self.y0 + 1..self.y1 {
+            points.push((self.x0, y));
+            points.push((self.x1, y));
+        }
+        points
+    }
+
+    fn interior(&self) -> impl Iterator<Item = (u32, u32)> {
+        let (x0, x1) = (self.x0, self.x1);
+        (self.y0 + 1..self.y1).flat_map(move |y| (x0 + 1..x1).map(move |x| (x, y)))
+    }
+
+    /// Quarters sharing their inner borders, so those are computed only once.
+    fn split(&self) -> [Rect; 4] {
+        let mx = (self.x0 + self.x1) / 2;
+        let my = (self.y0 + self.y1) / 2;
+        [
+            Rect{x0: self.x0, y0: self.y0, x1: mx, y1: my},
+            Rect{x0: mx, y0: self.y0, x1: self.x1, y1: my},
+            Rect{x0: self.x0, y0: my, x1: mx, y1: self.y1},
+            Rect{x0: mx, y0: my, x1: self.x1, y1: self.y1},
+        ]
+    }
+}
+
+
+pub struct SubdivisionRender {
+    view: View,
+    size: (u32, u32),
+    fractal: Fractal,
+    samples: Vec<Sample>,
+    /// Pixels that are computed or filled.
+    done: Vec<bool>,
+    pending: Vec<Rect>,
+}
+
+impl SubdivisionRender {
+    pub fn new(view: View, size: (u32, u32), fractal: Fractal) -> SubdivisionRender {
+        let pixel_count = size.0 as usize * size.1 as usize;
+        let pending = if pixel_count > 0 {
+            vec![Rect{x0: 0, y0: 0, x1: size.0 - 1, y1: size.1 - 1}]
+        } else {
+            Vec::new()
+        };
+        SubdivisionRender{
+            view,
+            size,
+            fractal,
+            samples: vec![Sample::INSIDE; pixel_count],
+            done: vec![false; pixel_count],
+            pending,
+        }
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        y as usize * self.size.0 as usize + x as usize
+    }
+
+    fn set_sample(&mut self, x: u32, y: u32, sample: Sample) {
+        let idx = self.index(x, y);
+        self.samples[idx] = sample;
+        self.done[idx] = true;
+    }
+
+    /// Iterates all points that have no result yet.
+    fn compute(&mut self, points: impl Iterator<Item = (u32, u32)>) {
+        let missing: Vec<(u32, u32)> = points.filter(|&(x, y)| !self.done[self.index(x, y)]).collect();
+        for chunk in missing.chunks(LANES) {
+            // Unused lanes get a point that escapes in the first iteration
+            let mut c_real = [4.0; LANES];
+            let mut c_imag = [0.0; LANES];
+            for (lane, &(x, y)) in chunk.iter().enumerate() {
+                (c_real[lane], c_imag[lane]) = self.view.pixel_to_complex(x as FracFloat, y as FracFloat, self.size);
+            }
+            for (&(x, y), escape) in chunk.iter().zip(escape_lanes(&self.fractal, &c_real, &c_imag)) {
+                self.set_sample(x, y, Sample::new(escape, self.view.scale));
+            }
+        }
+    }
+
+    fn process(&mut self, rect: Rect) {
+        let border = rect.border();
+        self.compute(border.iter().copied());
+        if rect.x1 - rect.x0 < MIN_SIZE || rect.y1 - rect.y0 < MIN_SIZE {
+            self.compute(rect.interior());
+            return;
+        }
+
+        let border_samples: Vec<Sample> = border.iter().map(|&(x, y)| self.samples[self.index(x, y)]).collect();
+        if border_samples.iter().all(|sample| sample.depth == border_samples[0].depth) {
+            // The inside is at least as close to the set as the closest border pixel
+            let fill = border_samples.into_iter()
+                .min_by(|a, b| a.dist.total_cmp(&b.dist))
+                .unwrap_or(Sample::INSIDE);
+            for (x, y) in rect.interior() {
+                self.set_sample(x, y, fill);
+            }
+        } else {
+            self.pending.extend(rect.split());
+        }
+    }
+}
+
+impl IncrementalRender for SubdivisionRender {
+    fn view(&self) -> &View {
+        &self.view
+    }
+
+    fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    fn fractal(&self) -> &Fractal {
+        &self.fractal
+    }
+
+    fn samples(&self) -> &[Sample] {
+        &self.samples
+    }
+
+    fn is_done(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    fn refine(&mut self, budget: Duration) {
+        let start = Instant::now();
+        while let Some(rect) = self.pending.pop() {
+            self.process(rect);
+            if start.elapsed() >= budget {
+                break;
+            }
+        }
+    }
+
+    /// Pending rectangles are clipped to the image, their missing borders are computed when they are processed.
+    fn pan(&mut self, view: View) -> bool {
+        let Some(shift) = pixel_shift(&self.view, &view) else {
+            return false;
+        };
+        shift_buffer(&mut self.samples, self.size, 1, shift, Sample::INSIDE);
+        shift_buffer(&mut self.done, self.size, 1, shift, false);
+        let size = self.size;
+        self.pending = self.pending.iter()
+            .filter_map(|rect| shift_region(&rect.to_region(), size, shift))
+            .chain(exposed_regions(size, shift))
+            .map(|region| Rect::from_region(&region))
+            .collect();
+        self.view = view;
+        true
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mandelbrot_depth;
+
+    #[test]
+    fn test_subdivision_matches_brute_force() {
+        let size = (160, 90);
+        let view = View::fit(size);
+        let mut render = SubdivisionRender::new(view, size, Fractal::default());
+        render.refine(Duration::MAX);
+        assert!(render.is_done());
+        assert!(render.done.iter().all(|&done| done));
+
+        let mut mismatches = 0;
+        for y in 0..size.1 {
+            for x in 0..size.0 {
+                let (c_real, c_imag) = view.pixel_to_complex(x as FracFloat, y as FracFloat, size);
+                let expected = mandelbrot_depth(c_real, c_imag).map_or(u32::MAX, |(depth, _)| depth);
+                if render.samples()[(y * size.0 + x) as usize].depth != expected {
+                    mismatches += 1;
+                }
+            }
+        }
+        // Filaments thinner than a pixel can slip through a rectangle border
+        assert!(mismatches * 200 < size.0 * size.1, "{} mismatched pixels", mismatches);
+    }
+
+    #[test]
+    fn test_pan_keeps_samples() {
+        let size = (64, 48);
+        let mut view = View::fit(size);
+        let mut render = SubdivisionRender::new(view, size, Fractal::default());
+        render.refine(Duration::MAX);
+        let before = render.samples().to_vec();
+        view.pan(-10.0, 4.0);
+        assert!(render.pan(view));
+        assert!(!render.is_done());
+        // Pixel (x, y) now shows former pixel (x + 10, y - 4)
+        assert_eq!(render.samples()[30 * 64 + 20], before[26 * 64 + 30]);
+        assert!(!render.done[30 * 64 + 60]);
+        render.refine(Duration::MAX);
+        assert!(render.done.iter().all(|&done| done));
+    }
+
+    #[test]
+    fn test_interior_is_filled() {
+        // Square inside the main cardioid
+        let view = View{center: (-0.1, 0.0), scale: 0.4 / 64.0, angle: 0.0};
+        let mut render = SubdivisionRender::new(view, (64, 64), Fractal::default());
+        let rect = render.pending.pop().unwrap();
+        render.process(rect);
+        assert!(render.pending.is_empty());
+        assert!(render.samples().iter().all(|sample| sample.is_inside()));
+    }
+}
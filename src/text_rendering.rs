@@ -2,119 +2,659 @@
 /// Fonts are rendered to an atlas with glyph location metadata using the font-kit library.
 /// This code should be multi-platform thanks to font-kit!
 
-use std::cmp::max;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
 
 use font_kit::family_name::FamilyName;
 use font_kit::font::Font;
 use font_kit::hinting::HintingOptions;
+use font_kit::outline::OutlineSink;
 use font_kit::properties::Properties;
 use font_kit::source::SystemSource;
 use font_kit::canvas::{Canvas, RasterizationOptions, Format};
+use font_kit::error::{FontLoadingError, GlyphLoadingError, SelectionError};
+use image::{GrayImage, ImageResult, RgbImage};
+use itertools::Itertools;
+use pathfinder_geometry::line_segment::LineSegment2F;
+use pathfinder_geometry::rect::RectI;
 use pathfinder_geometry::transform2d::Transform2F;
 use pathfinder_geometry::vector::{Vector2F, Vector2I};
 
+use crate::packer::SkylinePacker;
 
-struct PrintableChars {
-    current_char: char,
+
+/// Set of characters that are rendered into the font atlas up front.
+/// Written as a comma separated list of code points (`U+3C0`), ranges of code points or characters
+/// (`U+20-U+7E`, `α-ω`, `0-9`) and literal characters (`×≈π°`). Numbers without `U+` are characters too.
+#[derive(Clone, Debug)]
+pub struct CharSet {
+    ranges: Vec<RangeInclusive<u32>>,
 }
 
-impl PrintableChars {
-    fn new() -> PrintableChars {
-        PrintableChars{current_char: char::from_u32(32).unwrap()}
+impl CharSet {
+    /// All printable ASCII characters.
+    pub fn printable_ascii() -> CharSet {
+        CharSet{ranges: vec![32..=126]}
+    }
+
+    pub fn chars(&self) -> impl Iterator<Item = char> + '_ {
+        self.ranges.iter().cloned().flatten().filter_map(char::from_u32)
     }
 }
 
+impl Default for CharSet {
+    /// Printable ASCII, the greek alphabet and the symbols used in coordinate readouts.
+    fn default() -> CharSet {
+        let mut charset = CharSet::printable_ascii();
+        charset.ranges.extend([0x391..=0x3a9, 0x3b1..=0x3c9]);
+        charset.ranges.extend("°±×≈√∞".chars().map(|c| c as u32..=c as u32));
+        charset
+    }
+}
 
-impl Iterator for PrintableChars {
-    type Item = char;
+impl fmt::Display for CharSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, range) in self.ranges.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            if range.start() == range.end() {
+                write!(f, "U+{:04X}", range.start())?;
+            } else {
+                write!(f, "U+{:04X}-U+{:04X}", range.start(), range.end())?;
+            }
+        }
+        Ok(())
+    }
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let code = self.current_char as u32;
-        match code {
-            32..=126 => {
-                self.current_char = char::from_u32(code + 1).unwrap();
-                Some(char::from_u32(code).unwrap())
-            },
-            _ => None,
+fn parse_code_point(s: &str) -> Option<u32> {
+    u32::from_str_radix(s.strip_prefix("U+")?, 16).ok()
+}
+
+/// End of a range, a code point or a single character.
+fn parse_range_end(s: &str) -> Option<u32> {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c as u32),
+        _ => parse_code_point(s),
+    }
+}
+
+impl FromStr for CharSet {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<CharSet, String> {
+        let mut ranges = Vec::new();
+        for token in spec.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+            if let Some(code) = parse_code_point(token) {
+                ranges.push(code..=code);
+                continue;
+            }
+            if let Some((start, end)) = token.split_once('-') {
+                if let (Some(start), Some(end)) = (parse_range_end(start), parse_range_end(end)) {
+                    if start > end {
+                        return Err(format!("Empty character range '{}'", token));
+                    }
+                    ranges.push(start..=end);
+                    continue;
+                }
+            }
+            if token.starts_with("U+") {
+                return Err(format!("Invalid code point '{}', expected hexadecimal digits after 'U+'", token));
+            }
+            // Anything that is not a code point or a range is taken literally
+            ranges.extend(token.chars().map(|c| c as u32..=c as u32));
+        }
+        if ranges.is_empty() {
+            return Err(String::from("Character set is empty"));
         }
+        Ok(CharSet{ranges})
     }
 }
 
 
-pub fn load_default_fonts() -> Font {
-    let src = SystemSource::new();
-    let font = match src.select_by_postscript_name("DejaVuSans") {
-        Ok(font) => font,
-        Err(_) => src.select_best_match(&[FamilyName::SansSerif], &Properties::new()).unwrap()
-    };
-    font.load().unwrap()
-}
-
-
-/// Estimates the size of a square atlas necessary to contain all the font letters.
-/// Padding is one-sided.
-fn estimate_atlas_size(font: &Font, size: f32, padding: i32) -> (i32, i32){
-    let mut total_length = 0;
-    let mut max_height = 0;
-    for c in PrintableChars::new() {
-        let glyph_id = font.glyph_for_char(c).expect(&format!("Could not lookup glyph for '{}' ({})", c, c as u32));
-        // This function is not accurate. A test shows that it may underpredict the raster bounds by up to 2 px.
-        let raster_rect = font.raster_bounds(
-            glyph_id, size,
-            Transform2F::default(),
-            HintingOptions::Vertical(size),
-            RasterizationOptions::SubpixelAa
-        ).unwrap();
-        total_length += raster_rect.width() + padding;
-        max_height = max(max_height, raster_rect.height());
-    }
-    let total_area = total_length * max_height;
-    // Add three extra rows: One for characters that dont fit at the end of the line,
-    // and another for a possibly cut of row at the bottom, and one for margin :)
-    let side_length_guess = (total_area as f32).sqrt();
-    let side_length = (total_area as f32 + side_length_guess * (max_height as f32) * 2.0).sqrt() as i32;
-    return (side_length, side_length)
-}
-
-
-pub fn generate_atlas(font: &Font, size: f32, padding: Option<i32>) -> Canvas {
-    // 2px has been shown to be a safe minimum padding.
-    let padding = padding.unwrap_or(2);
-    let (canvas_w, canvas_h) = estimate_atlas_size(&font, size, padding);
-    log::debug!("Creating font atlas with dimensions {}x{} for \"{}\", {:.1}px", canvas_w, canvas_h, font.full_name(), size);
-    let mut canvas = Canvas::new(Vector2I::new(canvas_w, canvas_h), Format::Rgb24);
-    let mut transform_vec = Vector2F::new(0.0, 0.0);
-
-    for c in PrintableChars::new() {
-        let glyph_id = font.glyph_for_char(c).unwrap();
-
-        // Predict raster width
-        let raster_rect = font.raster_bounds(
-            glyph_id, size,
-            Transform2F::default(),
-            HintingOptions::Vertical(size),
-            RasterizationOptions::SubpixelAa
-        ).unwrap();
-        if raster_rect.width() == 0 { continue; }
-        
-        // Calculate offset
-        let mut new_x = transform_vec.x() as i32 + raster_rect.width() + padding;
-        if new_x >= canvas_w {
-            new_x = raster_rect.width() + padding;
-            transform_vec.set_x(0.0);
-            transform_vec.set_y(transform_vec.y() + size);
-        }
-
-        font.rasterize_glyph(
-            &mut canvas,
-            glyph_id,
+/// Location of a rendered glyph in the atlas.
+#[derive(Clone, Copy, Debug)]
+pub struct AtlasGlyph {
+    /// Index of the font in the fallback chain that provided the glyph.
+    pub font: usize,
+    pub glyph_id: u32,
+    /// Position and size of the glyph in the atlas canvas.
+    pub rect: RectI,
+    /// Offset of the glyph raster from the pen position on the baseline.
+    pub bearing: Vector2I,
+    /// Horizontal advance in pixels.
+    pub advance: f32,
+}
+
+
+/// Kind of glyph images stored in an atlas.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AtlasKind {
+    /// Subpixel antialiased RGB coverage, only looks right when drawn at the atlas size.
+    Bitmap,
+    /// Single channel signed distance field generated from the glyph outlines.
+    /// `spread` is the distance in pixels that is covered by the 0..1 value range on either side of the edge.
+    SignedDistance { spread: f32 },
+}
+
+
+#[derive(Clone, Debug)]
+pub enum AtlasError {
+    /// None of the fonts has a glyph for the character.
+    MissingGlyph(char),
+    GlyphLoading(char, GlyphLoadingError),
+    /// The glyph does not fit, even after growing the atlas to its maximum size.
+    Full { c: char, max_size: i32 },
+}
+
+impl fmt::Display for AtlasError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AtlasError::MissingGlyph(c) => write!(f, "No font provides a glyph for '{}' ({:#x})", c, *c as u32),
+            AtlasError::GlyphLoading(c, err) => write!(f, "Failed to load glyph for '{}' ({:#x}): {}", c, *c as u32, err),
+            AtlasError::Full { c, max_size } => write!(f, "Font atlas is full at {}x{}, cannot add '{}' ({:#x})", max_size, max_size, c, *c as u32),
+        }
+    }
+}
+
+impl std::error::Error for AtlasError {}
+
+
+/// A font atlas with glyphs from a chain of fallback fonts.
+/// Characters outside of the initial character set are rendered on demand.
+pub struct FontAtlas {
+    fonts: Vec<Font>,
+    kind: AtlasKind,
+    size: f32,
+    padding: i32,
+    max_size: i32,
+    packer: SkylinePacker,
+    canvas: Canvas,
+    // Failures are cached too, so that missing characters are only looked up once
+    glyphs: HashMap<char, Result<AtlasGlyph, AtlasError>>,
+    dirty: bool,
+}
+
+impl FontAtlas {
+    /// Side length of a new atlas, it doubles in width or height whenever a glyph does not fit.
+    const INITIAL_SIZE: i32 = 256;
+    /// Largest side length the atlas grows to. All GL 3 implementations support textures this large.
+    const MAX_SIZE: i32 = 8192;
+
+    /// Renders the given character set into a new atlas. `fonts` is the fallback chain,
+    /// the first font providing a glyph is used.
+    /// Characters that no font provides are skipped.
+    pub fn new(fonts: Vec<Font>, kind: AtlasKind, size: f32, padding: Option<i32>, charset: &CharSet) -> Result<FontAtlas, AtlasError> {
+        assert!(!fonts.is_empty(), "Font atlas requires at least one font");
+        // Glyphs are measured exactly, padding only prevents bleeding when sampling with filtering.
+        let padding = padding.unwrap_or(1);
+        let format = match kind {
+            AtlasKind::Bitmap => Format::Rgb24,
+            AtlasKind::SignedDistance { .. } => Format::A8,
+        };
+        log::debug!("Creating {:?} font atlas for \"{}\", {:.1}px", kind, fonts[0].full_name(), size);
+        let mut atlas = FontAtlas {
+            fonts,
+            kind,
             size,
-            Transform2F::from_translation(-raster_rect.origin().to_f32()) * Transform2F::from_translation(transform_vec),
-            HintingOptions::Vertical(size),
-            RasterizationOptions::SubpixelAa,
-        ).unwrap();
+            padding,
+            max_size: FontAtlas::MAX_SIZE,
+            packer: SkylinePacker::new(FontAtlas::INITIAL_SIZE, FontAtlas::INITIAL_SIZE),
+            canvas: Canvas::new(Vector2I::splat(FontAtlas::INITIAL_SIZE), format),
+            glyphs: HashMap::new(),
+            dirty: true,
+        };
+        for c in charset.chars() {
+            match atlas.glyph(c) {
+                Ok(_) => (),
+                Err(err @ AtlasError::Full { .. }) => return Err(err),
+                Err(err) => log::warn!("{}", err),
+            }
+        }
+        log::debug!("Font atlas holds {} glyphs in {}x{}", atlas.glyphs.len(), atlas.canvas.size.x(), atlas.canvas.size.y());
+        Ok(atlas)
+    }
+
+    pub fn canvas(&self) -> &Canvas {
+        &self.canvas
+    }
+
+    pub fn kind(&self) -> AtlasKind {
+        self.kind
+    }
+
+    /// Pixel size the glyphs were rendered at.
+    pub fn size(&self) -> f32 {
+        self.size
+    }
+
+    /// Distance from the top of a line to the baseline, in pixels.
+    pub fn ascent(&self) -> f32 {
+        let metrics = self.fonts[0].metrics();
+        metrics.ascent * self.size / metrics.units_per_em as f32
+    }
+
+    /// Distance between consecutive baselines, in pixels.
+    pub fn line_height(&self) -> f32 {
+        let metrics = self.fonts[0].metrics();
+        (metrics.ascent - metrics.descent + metrics.line_gap) * self.size / metrics.units_per_em as f32
+    }
+
+    /// Returns true once after glyphs have been added, so that uploaded copies can be refreshed.
+    pub fn take_dirty(&mut self) -> bool {
+        std::mem::replace(&mut self.dirty, false)
+    }
+
+    /// Writes the atlas canvas to an image file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> ImageResult<()> {
+        let (w, h) = (self.canvas.size.x() as u32, self.canvas.size.y() as u32);
+        match self.canvas.format {
+            Format::A8 => GrayImage::from_raw(w, h, self.canvas.pixels.clone()).unwrap().save(path),
+            _ => RgbImage::from_raw(w, h, self.canvas.pixels.clone()).unwrap().save(path),
+        }
+    }
+
+    /// Looks up the glyph for `c`, rendering it into the atlas if it is not present yet.
+    pub fn glyph(&mut self, c: char) -> Result<AtlasGlyph, AtlasError> {
+        if let Some(entry) = self.glyphs.get(&c) {
+            return entry.clone();
+        }
+        let entry = self.insert(c);
+        self.glyphs.insert(c, entry.clone());
+        entry
+    }
+
+    fn insert(&mut self, c: char) -> Result<AtlasGlyph, AtlasError> {
+        let (font_idx, glyph_id) = find_glyph(&self.fonts, c).ok_or(AtlasError::MissingGlyph(c))?;
+        let font = &self.fonts[font_idx];
+        let size = self.size;
+        let advance = font.advance(glyph_id).map(|a| a.x()).unwrap_or(0.0) * size / font.metrics().units_per_em as f32;
+
+        let glyph_image = match self.kind {
+            AtlasKind::Bitmap => rasterize_bitmap(font, glyph_id, size),
+            AtlasKind::SignedDistance { spread } => rasterize_distance_field(font, glyph_id, size, spread),
+        }.map_err(|err| AtlasError::GlyphLoading(c, err))?;
+        let Some((image, bearing)) = glyph_image else {
+            // Whitespace only needs the advance
+            return Ok(AtlasGlyph{font: font_idx, glyph_id, rect: RectI::default(), bearing: Vector2I::default(), advance});
+        };
+
+        let origin = self.allocate(image.size + Vector2I::splat(self.padding)).ok_or(AtlasError::Full{c, max_size: self.max_size})?;
+        copy_canvas(&image, &mut self.canvas, origin);
+        self.dirty = true;
+        Ok(AtlasGlyph{
+            font: font_idx,
+            glyph_id,
+            rect: RectI::new(origin, image.size),
+            bearing,
+            advance,
+        })
+    }
+
+    /// Reserves space in the atlas, growing the canvas to the next power of two when needed.
+    fn allocate(&mut self, size: Vector2I) -> Option<Vector2I> {
+        loop {
+            if let Some((x, y)) = self.packer.pack(size.x(), size.y()) {
+                return Some(Vector2I::new(x, y));
+            }
+            let (w, h) = self.packer.size();
+            let (w, h) = if w <= h { (w * 2, h) } else { (w, h * 2) };
+            if w > self.max_size || h > self.max_size {
+                return None;
+            }
+            log::debug!("Growing font atlas to {}x{}", w, h);
+            let mut canvas = Canvas::new(Vector2I::new(w, h), self.canvas.format);
+            copy_canvas(&self.canvas, &mut canvas, Vector2I::default());
+            self.canvas = canvas;
+            self.packer.grow(w, h);
+        }
+    }
+}
+
+
+/// Copies all of `src` into `dst` at `origin`. Both canvases must have the same format.
+fn copy_canvas(src: &Canvas, dst: &mut Canvas, origin: Vector2I) {
+    assert_eq!(src.format, dst.format);
+    let row_len = src.size.x() as usize * src.format.bytes_per_pixel() as usize;
+    let x_offset = origin.x() as usize * dst.format.bytes_per_pixel() as usize;
+    for row in 0..src.size.y() as usize {
+        let dst_start = (origin.y() as usize + row) * dst.stride + x_offset;
+        let src_start = row * src.stride;
+        dst.pixels[dst_start..dst_start + row_len].copy_from_slice(&src.pixels[src_start..src_start + row_len]);
+    }
+}
+
+
+/// Rasterizes a glyph and crops it to the pixels it actually covers.
+/// Returns the glyph image and its offset from the pen position, or `None` for blank glyphs.
+fn rasterize_bitmap(font: &Font, glyph_id: u32, size: f32) -> Result<Option<(Canvas, Vector2I)>, GlyphLoadingError> {
+    // The predicted raster bounds may be too small by up to 2 px, so leave some slack around them.
+    const SLACK: i32 = 2;
+    let raster_rect = font.raster_bounds(
+        glyph_id, size,
+        Transform2F::default(),
+        HintingOptions::Vertical(size),
+        RasterizationOptions::SubpixelAa
+    )?;
+    if raster_rect.width() == 0 || raster_rect.height() == 0 {
+        return Ok(None);
+    }
+    let mut scratch = Canvas::new(raster_rect.size() + Vector2I::splat(2 * SLACK), Format::Rgb24);
+    let scratch_origin = raster_rect.origin() - Vector2I::splat(SLACK);
+    font.rasterize_glyph(
+        &mut scratch,
+        glyph_id,
+        size,
+        Transform2F::from_translation(-scratch_origin.to_f32()),
+        HintingOptions::Vertical(size),
+        RasterizationOptions::SubpixelAa,
+    )?;
+
+    // Measure the rasterized glyph
+    let bpp = scratch.format.bytes_per_pixel() as usize;
+    let (w, h) = (scratch.size.x() as usize, scratch.size.y() as usize);
+    let covered = |x: usize, y: usize| scratch.pixels[y * scratch.stride + x * bpp..][..bpp].iter().any(|&v| v != 0);
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (w, h, 0, 0);
+    for (x, y) in (0..w).cartesian_product(0..h) {
+        if covered(x, y) {
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x + 1);
+            max_y = max_y.max(y + 1);
+        }
+    }
+    if min_x >= max_x {
+        return Ok(None);
+    }
+
+    let mut glyph = Canvas::new(Vector2I::new((max_x - min_x) as i32, (max_y - min_y) as i32), Format::Rgb24);
+    for row in min_y..max_y {
+        let src = row * scratch.stride + min_x * bpp;
+        let dst = (row - min_y) * glyph.stride;
+        glyph.pixels[dst..dst + glyph.stride].copy_from_slice(&scratch.pixels[src..src + glyph.stride]);
+    }
+    Ok(Some((glyph, scratch_origin + Vector2I::new(min_x as i32, min_y as i32))))
+}
+
+
+/// Generates a distance field image of the glyph outline, extending `spread` pixels past the outline.
+/// Returns the glyph image and its offset from the pen position, or `None` for blank glyphs.
+fn rasterize_distance_field(font: &Font, glyph_id: u32, size: f32, spread: f32) -> Result<Option<(Canvas, Vector2I)>, GlyphLoadingError> {
+    let mut sink = SegmentSink::new(size / font.metrics().units_per_em as f32);
+    font.outline(glyph_id, HintingOptions::None, &mut sink)?;
+    let Some(bounds) = outline_bounds(&sink.segments, spread.ceil() as i32) else {
+        return Ok(None);
+    };
+    let (w, h) = (bounds.width() as usize, bounds.height() as usize);
+    let mut glyph = Canvas::new(bounds.size(), Format::A8);
+    glyph.pixels = signed_distance_field(&sink.segments, bounds.min_x() as f32, bounds.min_y() as f32, w, h, spread);
+    Ok(Some((glyph, bounds.origin())))
+}
+
+
+/// Straight line segment of a flattened glyph outline, in pixels with the y axis pointing down.
+type Segment = ((f32, f32), (f32, f32));
+
+/// Collects glyph outlines as line segments, flattening curves.
+struct SegmentSink {
+    scale: f32,
+    segments: Vec<Segment>,
+    start: (f32, f32),
+    pen: (f32, f32),
+}
+
+impl SegmentSink {
+    /// Number of line segments a bezier curve is split into.
+    const CURVE_STEPS: usize = 8;
+
+    fn new(scale: f32) -> SegmentSink {
+        SegmentSink{scale, segments: Vec::new(), start: (0.0, 0.0), pen: (0.0, 0.0)}
+    }
+
+    fn point(&self, v: Vector2F) -> (f32, f32) {
+        // Outlines are y-up, the atlas is y-down
+        (v.x() * self.scale, -v.y() * self.scale)
+    }
+
+    fn push(&mut self, to: (f32, f32)) {
+        if to != self.pen {
+            self.segments.push((self.pen, to));
+        }
+        self.pen = to;
+    }
+}
+
+impl OutlineSink for SegmentSink {
+    fn move_to(&mut self, to: Vector2F) {
+        self.pen = self.point(to);
+        self.start = self.pen;
+    }
+
+    fn line_to(&mut self, to: Vector2F) {
+        let to = self.point(to);
+        self.push(to);
+    }
+
+    fn quadratic_curve_to(&mut self, ctrl: Vector2F, to: Vector2F) {
+        let (p0, p1, p2) = (self.pen, self.point(ctrl), self.point(to));
+        for step in 1..=Self::CURVE_STEPS {
+            let t = step as f32 / Self::CURVE_STEPS as f32;
+            let u = 1.0 - t;
+            self.push((
+                u*u*p0.0 + 2.0*u*t*p1.0 + t*t*p2.0,
+                u*u*p0.1 + 2.0*u*t*p1.1 + t*t*p2.1,
+            ));
+        }
+    }
+
+    fn cubic_curve_to(&mut self, ctrl: LineSegment2F, to: Vector2F) {
+        let (p0, p1, p2, p3) = (self.pen, self.point(ctrl.from()), self.point(ctrl.to()), self.point(to));
+        for step in 1..=Self::CURVE_STEPS {
+            let t = step as f32 / Self::CURVE_STEPS as f32;
+            let u = 1.0 - t;
+            self.push((
+                u*u*u*p0.0 + 3.0*u*u*t*p1.0 + 3.0*u*t*t*p2.0 + t*t*t*p3.0,
+                u*u*u*p0.1 + 3.0*u*u*t*p1.1 + 3.0*u*t*t*p2.1 + t*t*t*p3.1,
+            ));
+        }
+    }
+
+    fn close(&mut self) {
+        let start = self.start;
+        self.push(start);
+    }
+}
+
+
+/// Integer pixel bounds of a set of segments, grown by `margin` on all sides.
+fn outline_bounds(segments: &[Segment], margin: i32) -> Option<RectI> {
+    let mut points = segments.iter().flat_map(|&(a, b)| [a, b]);
+    let first = points.next()?;
+    let (min, max) = points.fold((first, first), |(min, max), p| {
+        ((min.0.min(p.0), min.1.min(p.1)), (max.0.max(p.0), max.1.max(p.1)))
+    });
+    Some(RectI::from_points(
+        Vector2I::new(min.0.floor() as i32 - margin, min.1.floor() as i32 - margin),
+        Vector2I::new(max.0.ceil() as i32 + margin, max.1.ceil() as i32 + margin),
+    ))
+}
+
+
+fn segment_distance(p: (f32, f32), (a, b): Segment) -> f32 {
+    let ab = (b.0 - a.0, b.1 - a.1);
+    let ap = (p.0 - a.0, p.1 - a.1);
+    let len_sq = ab.0*ab.0 + ab.1*ab.1;
+    let t = if len_sq > 0.0 { ((ap.0*ab.0 + ap.1*ab.1) / len_sq).clamp(0.0, 1.0) } else { 0.0 };
+    let d = (ap.0 - t*ab.0, ap.1 - t*ab.1);
+    (d.0*d.0 + d.1*d.1).sqrt()
+}
+
+
+/// Non-zero winding number of the closed outline around `p`.
+fn winding_number(p: (f32, f32), segments: &[Segment]) -> i32 {
+    let mut winding = 0;
+    for &(a, b) in segments {
+        let cross = (b.0 - a.0) * (p.1 - a.1) - (p.0 - a.0) * (b.1 - a.1);
+        if a.1 <= p.1 && b.1 > p.1 && cross > 0.0 {
+            winding += 1;
+        } else if b.1 <= p.1 && a.1 > p.1 && cross < 0.0 {
+            winding -= 1;
+        }
+    }
+    winding
+}
+
+
+/// Computes a `w` x `h` signed distance field of the outline, sampled at pixel centers starting at (`x0`, `y0`).
+/// The edge maps to 128, with values increasing towards the inside of the glyph and saturating at `spread` pixels.
+fn signed_distance_field(segments: &[Segment], x0: f32, y0: f32, w: usize, h: usize, spread: f32) -> Vec<u8> {
+    let mut field = vec![0u8; w * h];
+    for (i, value) in field.iter_mut().enumerate() {
+        let p = (x0 + (i % w) as f32 + 0.5, y0 + (i / w) as f32 + 0.5);
+        let dist = segments.iter().map(|&s| segment_distance(p, s)).fold(f32::INFINITY, f32::min);
+        let signed = if winding_number(p, segments) != 0 { dist } else { -dist };
+        *value = ((0.5 + 0.5 * signed / spread).clamp(0.0, 1.0) * 255.0).round() as u8;
+    }
+    field
+}
+
+
+/// Finds the first font in the fallback chain that has a glyph for `c`.
+fn find_glyph(fonts: &[Font], c: char) -> Option<(usize, u32)> {
+    fonts.iter().enumerate().find_map(|(i, font)| font.glyph_for_char(c).map(|glyph_id| (i, glyph_id)))
+}
+
+
+/// Font bundled with the binary, used when no system font is available.
+const EMBEDDED_FONT: &[u8] = include_bytes!("../res/DejaVuSans.ttf");
+
+
+#[derive(Debug)]
+pub enum FontError {
+    /// No font with the requested family name is installed.
+    Selection(String, SelectionError),
+    Loading(String, FontLoadingError),
+}
+
+impl fmt::Display for FontError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FontError::Selection(name, err) => write!(f, "Could not find font \"{}\": {}", name, err),
+            FontError::Loading(name, err) => write!(f, "Could not load font \"{}\": {}", name, err),
+        }
+    }
+}
+
+impl std::error::Error for FontError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FontError::Selection(_, err) => Some(err),
+            FontError::Loading(_, err) => Some(err),
+        }
+    }
+}
+
+
+/// Loads the font for `name`, which is either a path to a TTF/OTF file or an installed family name.
+pub fn load_font(name: &str) -> Result<Font, FontError> {
+    if Path::new(name).is_file() {
+        return Font::from_path(name, 0).map_err(|err| FontError::Loading(name.to_string(), err));
+    }
+    let handle = SystemSource::new()
+        .select_best_match(&[FamilyName::Title(name.to_string())], &Properties::new())
+        .map_err(|err| FontError::Selection(name.to_string(), err))?;
+    handle.load().map_err(|err| FontError::Loading(name.to_string(), err))
+}
+
+
+/// Loads the primary UI font followed by fallback fonts for characters it does not cover.
+/// The primary font is `font` if given, otherwise the system sans-serif font.
+/// The embedded font always ends the chain, so this only fails if a requested font cannot be loaded.
+pub fn load_fonts(font: Option<&str>) -> Result<Vec<Font>, FontError> {
+    let mut fonts = Vec::new();
+    if let Some(name) = font {
+        fonts.push(load_font(name)?);
+    }
+
+    let src = SystemSource::new();
+    let system_families = [
+        FamilyName::Title(String::from("DejaVu Sans")), FamilyName::SansSerif, FamilyName::Serif,
+        FamilyName::Monospace, FamilyName::Title(String::from("Noto Sans Symbols")),
+    ];
+    for family in system_families {
+        let Ok(handle) = src.select_best_match(&[family], &Properties::new()) else { continue };
+        match handle.load() {
+            Ok(fallback) => {
+                if !fonts.iter().any(|f| f.full_name() == fallback.full_name()) {
+                    log::debug!("Using system font \"{}\"", fallback.full_name());
+                    fonts.push(fallback);
+                }
+            },
+            Err(err) => log::debug!("Failed to load system font: {}", err),
+        }
+    }
+    if fonts.is_empty() {
+        log::info!("No system fonts found, using the embedded font");
+    }
+
+    let embedded = Font::from_bytes(Arc::new(EMBEDDED_FONT.to_vec()), 0)
+        .map_err(|err| FontError::Loading(String::from("embedded"), err))?;
+    fonts.push(embedded);
+    Ok(fonts)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_charset_parse() {
+        let charset: CharSet = "U+20-U+7E,α-ω,U+b0,×≈".parse().unwrap();
+        let chars: Vec<char> = charset.chars().collect();
+        assert_eq!(chars.len(), 95 + 25 + 3);
+        assert!(chars.contains(&'π'));
+        assert!(chars.contains(&'°'));
+        assert!(chars.contains(&'≈'));
+        assert!("U+7E-U+20".parse::<CharSet>().is_err());
+        assert!("U+xyz".parse::<CharSet>().is_err());
+        assert!("".parse::<CharSet>().is_err());
+    }
+
+    #[test]
+    fn test_charset_numbers_are_characters() {
+        let digits: CharSet = "0-9".parse().unwrap();
+        assert_eq!(digits.chars().collect::<String>(), "0123456789");
+        let literal: CharSet = "42,-".parse().unwrap();
+        assert_eq!(literal.chars().collect::<String>(), "42-");
+    }
+
+    #[test]
+    fn test_charset_display_roundtrip() {
+        let charset = CharSet::default();
+        let parsed: CharSet = charset.to_string().parse().unwrap();
+        assert!(charset.chars().eq(parsed.chars()));
+    }
 
-        transform_vec.set_x(new_x as f32);
+    #[test]
+    fn test_signed_distance_field() {
+        let square = [
+            ((0.0, 0.0), (4.0, 0.0)), ((4.0, 0.0), (4.0, 4.0)),
+            ((4.0, 4.0), (0.0, 4.0)), ((0.0, 4.0), (0.0, 0.0)),
+        ];
+        assert_eq!(outline_bounds(&square, 2), Some(RectI::new(Vector2I::new(-2, -2), Vector2I::new(8, 8))));
+        let field = signed_distance_field(&square, -2.0, -2.0, 8, 8, 2.0);
+        // Pixel centers half a pixel inside and outside of the left edge
+        assert_eq!(field[4*8 + 2], 159);
+        assert_eq!(field[4*8 + 1], 96);
+        // Deep inside and far outside saturate
+        assert_eq!(field[4*8 + 4], 223);
+        assert_eq!(field[0], 0);
     }
-    canvas
 }
@@ -0,0 +1,157 @@
+/// Tiled offscreen rendering of images too large to hold in memory at once.
+/// The tiles of a row are rendered in parallel, then the row is streamed to the output files
+/// before the next one starts. Memory use only depends on the image width and the tile size.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use png::{BitDepth as PngBitDepth, ColorType, Encoder, StreamWriter};
+
+use crate::coloring::{Coloring, Sample};
+use crate::export::ExportError;
+use crate::location::Location;
+use crate::progressive::CpuStrategy;
+use crate::render::BitDepth;
+use crate::view::View;
+use crate::FracFloat;
+
+
+/// View of the part of an image of `size` pixels that starts at `origin`, with every pixel showing the same point.
+pub fn tile_view(view: &View, size: (u32, u32), origin: (u32, u32), tile_size: (u32, u32)) -> View {
+    let center = view.pixel_to_complex(
+        origin.0 as FracFloat + 0.5 * tile_size.0 as FracFloat,
+        origin.1 as FracFloat + 0.5 * tile_size.1 as FracFloat,
+        size,
+    );
+    View{center, ..*view}
+}
+
+
+/// Pixel ranges of the tiles along an image side of `len` pixels.
+pub fn tile_ranges(len: u32, tile: u32) -> impl Iterator<Item = Range<u32>> {
+    (0..len).step_by(tile as usize).map(move |start| start..(start + tile).min(len))
+}
+
+
+/// Samples of the image rows `rows`, rendered as tiles of `tile` pixels width in parallel.
+/// A worker per core takes the next column from a shared counter, so small tiles do not mean many threads.
+pub fn render_tile_row(strategy: CpuStrategy, location: &Location, rows: Range<u32>, tile: u32) -> Vec<Sample> {
+    let (view, size, fractal) = (&location.view, location.size, location.fractal);
+    let height = rows.end - rows.start;
+    let columns: Vec<Range<u32>> = tile_ranges(size.0, tile).collect();
+    let workers = thread::available_parallelism().map_or(1, |n| n.get()).min(columns.len());
+    let next = AtomicUsize::new(0);
+    let mut tiles: Vec<(usize, Vec<Sample>)> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| scope.spawn(|| {
+                let mut done = Vec::new();
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(columns) = columns.get(index) else {
+                        return done;
+                    };
+                    let tile_size = (columns.end - columns.start, height);
+                    let view = tile_view(view, size, (columns.start, rows.start), tile_size);
+                    let mut render = strategy.start(view, tile_size, fractal);
+                    render.refine(Duration::MAX);
+                    done.push((index, render.samples().to_vec()));
+                }
+            }))
+            .collect();
+        handles.into_iter().flat_map(|handle| handle.join().expect("tile render panicked")).collect()
+    });
+    tiles.sort_unstable_by_key(|&(index, _)| index);
+
+    // Interleave the tiles into image rows
+    let mut samples = Vec::with_capacity(size.0 as usize * height as usize);
+    for y in 0..height as usize {
+        for ((_, tile_samples), columns) in tiles.iter().zip(&columns) {
+            let width = (columns.end - columns.start) as usize;
+            samples.extend_from_slice(&tile_samples[y * width..(y + 1) * width]);
+        }
+    }
+    samples
+}
+
+
+/// PNG written row by row, with the location in text chunks.
+pub struct PngRows {
+    stream: StreamWriter<'static, BufWriter<File>>,
+    coloring: Coloring,
+    depth: BitDepth,
+}
+
+impl PngRows {
+    /// Only 8 and 16 bits per channel are supported by PNG.
+    pub fn create(path: &Path, location: &Location, depth: BitDepth) -> Result<PngRows, ExportError> {
+        let mut encoder = Encoder::new(BufWriter::new(File::create(path)?), location.size.0, location.size.1);
+        encoder.set_color(ColorType::Rgb);
+        encoder.set_depth(match depth {
+            BitDepth::Sixteen => PngBitDepth::Sixteen,
+            _ => PngBitDepth::Eight,
+        });
+        for (key, value) in location.entries() {
+            encoder.add_text_chunk(key.to_string(), value)?;
+        }
+        let stream = encoder.write_header()?.into_stream_writer()?;
+        Ok(PngRows{stream, coloring: location.coloring, depth})
+    }
+
+    /// Colors and appends complete image rows.
+    pub fn write(&mut self, samples: &[Sample]) -> Result<(), ExportError> {
+        let colors = samples.iter().map(|sample| self.coloring.color_f32(sample));
+        let bytes: Vec<u8> = match self.depth {
+            BitDepth::Sixteen => colors.flat_map(|rgb| rgb.map(|v| (v * 65535.0).round() as u16))
+                .flat_map(u16::to_be_bytes)
+                .collect(),
+            _ => colors.flat_map(|rgb| rgb.map(|v| (v * 255.0).round() as u8)).collect(),
+        };
+        self.stream.write_all(&bytes)?;
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<(), ExportError> {
+        self.stream.finish()?;
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fractal::Fractal;
+
+    #[test]
+    fn test_tiles_match_full_render() {
+        let size = (100, 70);
+        let mut view = View::at((-0.5, 0.1), 1.5, size);
+        view.rotate(0.4);
+        let fractal = Fractal::default();
+        let mut full = CpuStrategy::Progressive.start(view, size, fractal);
+        full.refine(Duration::MAX);
+
+        let location = Location{view, size, fractal, coloring: Default::default()};
+        // Small tiles give more columns than workers
+        for tile in [32, 8] {
+            let tiled: Vec<Sample> = tile_ranges(size.1, tile)
+                .flat_map(|rows| render_tile_row(CpuStrategy::Progressive, &location, rows, tile))
+                .collect();
+            assert_eq!(tiled.len(), full.samples().len());
+            // Tile centers can round differently in the last bit, which may change single pixels on an iteration boundary
+            let mismatches = tiled.iter().zip(full.samples()).filter(|(a, b)| a.depth != b.depth).count();
+            assert!(mismatches * 1000 < tiled.len(), "{} mismatched pixels", mismatches);
+        }
+    }
+
+    #[test]
+    fn test_tile_ranges_cover_image() {
+        assert_eq!(tile_ranges(70, 32).collect::<Vec<_>>(), vec![0..32, 32..64, 64..70]);
+        assert_eq!(tile_ranges(64, 32).count(), 2);
+    }
+}
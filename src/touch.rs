@@ -0,0 +1,122 @@
+/// Touchscreen navigation of the interactive modes.
+/// One finger pans, two fingers pinch to zoom and twist to rotate around their midpoint.
+/// SDL's emulated mouse events are turned off, so that touches do not also pan through `navigate`.
+
+use sdl2::event::Event;
+
+use crate::view::View;
+use crate::FracFloat;
+
+
+type Point = (FracFloat, FracFloat);
+/// Finger ids are only unique per touch device.
+type FingerId = (i64, i64);
+
+
+#[derive(Default)]
+pub struct Touch {
+    /// Fingers on the screen with their positions in pixels, in the order they touched down.
+    fingers: Vec<(FingerId, Point)>,
+}
+
+impl Touch {
+    pub fn fingers(&self) -> usize {
+        self.fingers.len()
+    }
+
+    /// Navigates the view of a window of `size` pixels with finger events.
+    pub fn handle_event(&mut self, event: &Event, view: &mut View, size: (u32, u32)) {
+        // Finger positions are normalized to the window
+        let to_pixels = |x: f32, y: f32| (x as FracFloat * size.0 as FracFloat, y as FracFloat * size.1 as FracFloat);
+        match *event {
+            Event::FingerDown {touch_id, finger_id, x, y, ..} => {
+                self.fingers.retain(|(id, _)| *id != (touch_id, finger_id));
+                self.fingers.push(((touch_id, finger_id), to_pixels(x, y)));
+            },
+            Event::FingerMotion {touch_id, finger_id, x, y, ..} => self.finger_moved((touch_id, finger_id), to_pixels(x, y), view, size),
+            Event::FingerUp {touch_id, finger_id, ..} => self.fingers.retain(|(id, _)| *id != (touch_id, finger_id)),
+            _ => (),
+        }
+    }
+
+    fn finger_moved(&mut self, finger_id: FingerId, to: Point, view: &mut View, size: (u32, u32)) {
+        let Some(index) = self.fingers.iter().position(|(id, _)| *id == finger_id) else {
+            return;
+        };
+        let from = self.fingers[index].1;
+        self.fingers[index].1 = to;
+        match (self.fingers.len(), index) {
+            (1, _) => view.pan(to.0 - from.0, to.1 - from.1),
+            // Further fingers are ignored
+            (_, 0 | 1) => {
+                let other = self.fingers[1 - index].1;
+                pinch(view, size, (from, other), (to, other));
+            },
+            _ => (),
+        }
+    }
+}
+
+
+/// Moves the view so that the points under two fingers at `before` stay under them at `after`,
+/// as far as a zoom and rotation allow.
+fn pinch(view: &mut View, size: (u32, u32), before: (Point, Point), after: (Point, Point)) {
+    let midpoint = |(a, b): (Point, Point)| (0.5 * (a.0 + b.0), 0.5 * (a.1 + b.1));
+    // Vector between the fingers, with y pointing up
+    let span = |(a, b): (Point, Point)| (b.0 - a.0, a.1 - b.1);
+    let (from, to) = (midpoint(before), midpoint(after));
+    let (span_before, span_after) = (span(before), span(after));
+    let length_before = span_before.0.hypot(span_before.1);
+    let length_after = span_after.0.hypot(span_after.1);
+    if length_before < 1.0 || length_after < 1.0 {
+        return;
+    }
+
+    view.pan(to.0 - from.0, to.1 - from.1);
+    let anchor = view.pixel_to_complex(to.0, to.1, size);
+    view.zoom(length_after / length_before);
+    view.rotate(span_after.1.atan2(span_after.0) - span_before.1.atan2(span_before.0));
+    // Zoom and rotation are around the window center, move the anchor back under the fingers
+    let moved = view.pixel_to_complex(to.0, to.1, size);
+    view.center.0 += anchor.0 - moved.0;
+    view.center.1 += anchor.1 - moved.1;
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Point, b: Point) -> bool {
+        (a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9
+    }
+
+    #[test]
+    fn test_pinch_keeps_points_under_fingers() {
+        let size = (800, 600);
+        let mut view = View::fit(size);
+        let before = ((300.0, 200.0), (500.0, 350.0));
+        let after = ((250.0, 220.0), (520.0, 420.0));
+        let under = (view.pixel_to_complex(before.0.0, before.0.1, size), view.pixel_to_complex(before.1.0, before.1.1, size));
+        pinch(&mut view, size, before, after);
+        // A similarity transform maps any two points onto any other two
+        assert!(close(view.pixel_to_complex(after.0.0, after.0.1, size), under.0));
+        assert!(close(view.pixel_to_complex(after.1.0, after.1.1, size), under.1));
+        assert!(view.angle != 0.0);
+    }
+
+    #[test]
+    fn test_one_finger_pans() {
+        let size = (800, 600);
+        let mut view = View::fit(size);
+        let mut touch = Touch::default();
+        touch.fingers.push(((1, 7), (100.0, 100.0)));
+        let under = view.pixel_to_complex(100.0, 100.0, size);
+        touch.finger_moved((1, 7), (130.0, 80.0), &mut view, size);
+        assert!(close(view.pixel_to_complex(130.0, 80.0, size), under));
+        // Unknown fingers do nothing, also with the same id on another device
+        touch.finger_moved((1, 8), (0.0, 0.0), &mut view, size);
+        touch.finger_moved((2, 7), (0.0, 0.0), &mut view, size);
+        assert!(close(view.pixel_to_complex(130.0, 80.0, size), under));
+    }
+}
@@ -0,0 +1,222 @@
+/// Mapping between window pixels and the complex plane.
+/// Pixel coordinates have their origin at the top left with y pointing down,
+/// while the imaginary axis points up, turned by the angle of the view.
+
+use std::f64::consts::TAU;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::FracFloat;
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct View {
+    /// Complex plane coordinates of the window center.
+    pub center: (FracFloat, FracFloat),
+    /// Complex plane units per pixel.
+    pub scale: FracFloat,
+    /// Counterclockwise rotation of the image in radians, in 0..2π.
+    pub angle: FracFloat,
+}
+
+impl View {
+    /// View centered on the origin, showing -1..1 along the shorter window side.
+    pub fn fit(size: (u32, u32)) -> View {
+        View{center: (0.0, 0.0), scale: 2.0 / u32::min(size.0, size.1).max(1) as FracFloat, angle: 0.0}
+    }
+
+    /// View centered on `center`, magnified by `zoom` relative to `View::fit`.
+    pub fn at(center: (FracFloat, FracFloat), zoom: FracFloat, size: (u32, u32)) -> View {
+        View{center, scale: View::fit(size).scale / zoom, angle: 0.0}
+    }
+
+    /// Magnification relative to `View::fit`.
+    pub fn zoom_level(&self, size: (u32, u32)) -> FracFloat {
+        View::fit(size).scale / self.scale
+    }
+
+    pub fn pixel_to_complex(&self, x: FracFloat, y: FracFloat, size: (u32, u32)) -> (FracFloat, FracFloat) {
+        let offset = self.pixels_to_plane(x - 0.5 * size.0 as FracFloat, y - 0.5 * size.1 as FracFloat);
+        (self.center.0 + offset.0, self.center.1 + offset.1)
+    }
+
+    /// Pixel position of a point in the complex plane, the inverse of `pixel_to_complex`.
+    pub fn complex_to_pixel(&self, re: FracFloat, im: FracFloat, size: (u32, u32)) -> (FracFloat, FracFloat) {
+        let offset = self.plane_to_pixels(re - self.center.0, im - self.center.1);
+        (offset.0 + 0.5 * size.0 as FracFloat, offset.1 + 0.5 * size.1 as FracFloat)
+    }
+
+    /// Complex plane offset of a pixel offset (`dx`, `dy`).
+    pub fn pixels_to_plane(&self, dx: FracFloat, dy: FracFloat) -> (FracFloat, FracFloat) {
+        let (sin, cos) = self.angle.sin_cos();
+        let (re, im) = (dx * self.scale, -dy * self.scale);
+        (re * cos + im * sin, im * cos - re * sin)
+    }
+
+    /// Pixel offset of a complex plane offset, the inverse of `pixels_to_plane`.
+    pub fn plane_to_pixels(&self, re: FracFloat, im: FracFloat) -> (FracFloat, FracFloat) {
+        let (sin, cos) = self.angle.sin_cos();
+        ((re * cos - im * sin) / self.scale, -(re * sin + im * cos) / self.scale)
+    }
+
+    /// Moves the view along with a mouse drag of (`dx`, `dy`) pixels.
+    pub fn pan(&mut self, dx: FracFloat, dy: FracFloat) {
+        let offset = self.pixels_to_plane(dx, dy);
+        self.center.0 -= offset.0;
+        self.center.1 -= offset.1;
+    }
+
+    /// Turns the image around the window center, counterclockwise for positive angles.
+    pub fn rotate(&mut self, radians: FracFloat) {
+        self.angle = (self.angle + radians).rem_euclid(TAU);
+    }
+
+    /// Zooms around the window center. Factors above 1 zoom in.
+    pub fn zoom(&mut self, factor: FracFloat) {
+        self.scale /= factor;
+    }
+
+    /// Adapts the view to a new window size, keeping the center and the extent along the shorter side.
+    pub fn resize(&mut self, old_size: (u32, u32), new_size: (u32, u32)) {
+        let old_min = u32::min(old_size.0, old_size.1).max(1) as FracFloat;
+        let new_min = u32::min(new_size.0, new_size.1).max(1) as FracFloat;
+        self.scale *= old_min / new_min;
+    }
+}
+
+
+/// Conversion between window coordinates, which input events use, and drawable pixels,
+/// which rendering uses. On HiDPI displays one window unit spans multiple pixels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PixelScale {
+    pub x: FracFloat,
+    pub y: FracFloat,
+}
+
+impl PixelScale {
+    pub fn new(window_size: (u32, u32), drawable_size: (u32, u32)) -> PixelScale {
+        PixelScale{
+            x: drawable_size.0 as FracFloat / window_size.0.max(1) as FracFloat,
+            y: drawable_size.1 as FracFloat / window_size.1.max(1) as FracFloat,
+        }
+    }
+
+    pub fn to_pixels(self, x: FracFloat, y: FracFloat) -> (FracFloat, FracFloat) {
+        (x * self.x, y * self.y)
+    }
+}
+
+
+/// Window dimensions given as `WIDTHxHEIGHT`, in window coordinates.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WindowSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Default for WindowSize {
+    fn default() -> WindowSize {
+        WindowSize{width: 1920, height: 1080}
+    }
+}
+
+impl fmt::Display for WindowSize {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+impl FromStr for WindowSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<WindowSize, String> {
+        let (width, height) = s.split_once(['x', 'X']).ok_or_else(|| format!("Expected WIDTHxHEIGHT, got '{}'", s))?;
+        let parse = |v: &str| v.trim().parse::<u32>().ok().filter(|&v| v > 0).ok_or_else(|| format!("Invalid window dimension '{}'", v));
+        Ok(WindowSize{width: parse(width)?, height: parse(height)?})
+    }
+}
+
+
+/// Point in the complex plane given as `RE,IM`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Center {
+    pub re: FracFloat,
+    pub im: FracFloat,
+}
+
+impl fmt::Display for Center {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{},{}", self.re, self.im)
+    }
+}
+
+impl FromStr for Center {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Center, String> {
+        let (re, im) = s.split_once(',').ok_or_else(|| format!("Expected RE,IM, got '{}'", s))?;
+        let parse = |v: &str| v.trim().parse::<FracFloat>().ok().filter(|v| v.is_finite()).ok_or_else(|| format!("Invalid coordinate '{}'", v));
+        Ok(Center{re: parse(re)?, im: parse(im)?})
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_view_resize_keeps_region() {
+        let mut view = View::fit((1920, 1080));
+        view.pan(100.0, -50.0);
+        let center = view.center;
+        let top = view.pixel_to_complex(960.0, 0.0, (1920, 1080));
+        view.resize((1920, 1080), (960, 540));
+        assert_eq!(view.center, center);
+        let new_top = view.pixel_to_complex(480.0, 0.0, (960, 540));
+        assert!((new_top.1 - top.1).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_view_rotation() {
+        let mut view = View::fit((200, 100));
+        view.rotate(std::f64::consts::FRAC_PI_2);
+        // Turned counterclockwise, the positive real axis points up
+        let top = view.pixel_to_complex(100.0, 0.0, (200, 100));
+        assert!((top.0 - 1.0).abs() < 1e-12 && top.1.abs() < 1e-12);
+        let offset = view.pixels_to_plane(3.0, -4.0);
+        let back = view.plane_to_pixels(offset.0, offset.1);
+        assert!((back.0 - 3.0).abs() < 1e-12 && (back.1 + 4.0).abs() < 1e-12);
+        let pixel = view.complex_to_pixel(top.0, top.1, (200, 100));
+        assert!((pixel.0 - 100.0).abs() < 1e-9 && pixel.1.abs() < 1e-9);
+        let mut panned = view;
+        panned.pan(10.0, 0.0);
+        let moved = panned.pixel_to_complex(110.0, 50.0, (200, 100));
+        assert!(moved.0.abs() < 1e-12 && moved.1.abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_pixel_scale() {
+        let scale = PixelScale::new((1280, 720), (2560, 1440));
+        assert_eq!(scale.to_pixels(10.0, -3.0), (20.0, -6.0));
+        assert_eq!(PixelScale::new((800, 600), (800, 600)).to_pixels(3.0, 4.0), (3.0, 4.0));
+    }
+
+    #[test]
+    fn test_window_size_parse() {
+        assert_eq!("800x600".parse(), Ok(WindowSize{width: 800, height: 600}));
+        assert!("800".parse::<WindowSize>().is_err());
+        assert!("0x600".parse::<WindowSize>().is_err());
+        assert_eq!(WindowSize::default().to_string().parse(), Ok(WindowSize::default()));
+    }
+
+    #[test]
+    fn test_center_parse() {
+        assert_eq!("-0.745, 0.105".parse(), Ok(Center{re: -0.745, im: 0.105}));
+        assert!("-0.745".parse::<Center>().is_err());
+        let center = Center{re: -1.985424253, im: 1e-20};
+        assert_eq!(center.to_string().parse(), Ok(center));
+        let view = View::at((center.re, center.im), 8.0, (640, 480));
+        assert!((view.zoom_level((640, 480)) - 8.0).abs() < 1e-12);
+    }
+}